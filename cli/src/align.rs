@@ -1,10 +1,43 @@
-pub fn display<R: Rectangular>(new_region: &mut R, other_displays: impl Iterator<Item = R>) {
+use serde::Serialize;
+
+/// Tuning of how [`display`] attaches a display to its neighbours.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AlignOptions {
+    /// Distance, in logical pixels, within which edges are snapped into alignment, and
+    /// the least that a display stays attached along the edge of its neighbour.
+    pub snap_threshold: f32,
+}
+
+impl AlignOptions {
+    /// Options for an output with the given scale, snapping within the default distance
+    /// in physical rather than logical pixels, so that high-DPI outputs snap as finely.
+    pub fn for_scale(scale: f64) -> Self {
+        Self {
+            snap_threshold: Self::default().snap_threshold / scale.max(1.0) as f32,
+        }
+    }
+}
+
+impl Default for AlignOptions {
+    fn default() -> Self {
+        Self {
+            snap_threshold: 4.0,
+        }
+    }
+}
+
+pub fn display<R: Rectangular>(
+    new_region: &mut R,
+    other_displays: impl Iterator<Item = R>,
+    options: AlignOptions,
+) {
+    let other_displays = other_displays.collect::<Vec<_>>();
     let mut nearest = f32::MAX;
-    let mut nearest_region = R::default();
+    let mut nearest_index = None;
     let mut nearest_side = NearestSide::East;
 
     // Find the nearest adjacent display to the display.
-    for other_display in other_displays {
+    for (index, other_display) in other_displays.iter().enumerate() {
         let center = new_region.center();
 
         let eastward = distance(other_display.east_point(), center) * 1.25;
@@ -31,10 +64,13 @@ pub fn display<R: Rectangular>(new_region: &mut R, other_displays: impl Iterator
         }
 
         if nearer {
-            nearest_region = other_display;
+            nearest_index = Some(index);
         }
     }
 
+    let default_region = R::default();
+    let nearest_region = nearest_index.map_or(&default_region, |index| &other_displays[index]);
+
     // Attach display to nearest adjacent display.
     match nearest_side {
         NearestSide::East => {
@@ -42,8 +78,8 @@ pub fn display<R: Rectangular>(new_region: &mut R, other_displays: impl Iterator
             new_region.set_y(
                 new_region
                     .y()
-                    .max(nearest_region.y() - new_region.height() + 4.0)
-                    .min(nearest_region.y() + nearest_region.height() - 4.0),
+                    .max(nearest_region.y() - new_region.height() + options.snap_threshold)
+                    .min(nearest_region.y() + nearest_region.height() - options.snap_threshold),
             );
         }
 
@@ -52,8 +88,8 @@ pub fn display<R: Rectangular>(new_region: &mut R, other_displays: impl Iterator
             new_region.set_x(
                 new_region
                     .x()
-                    .max(nearest_region.x() - new_region.width() + 4.0)
-                    .min(nearest_region.x() + nearest_region.width() - 4.0),
+                    .max(nearest_region.x() - new_region.width() + options.snap_threshold)
+                    .min(nearest_region.x() + nearest_region.width() - options.snap_threshold),
             );
         }
 
@@ -62,8 +98,8 @@ pub fn display<R: Rectangular>(new_region: &mut R, other_displays: impl Iterator
             new_region.set_y(
                 new_region
                     .y()
-                    .max(nearest_region.y() - new_region.height() + 4.0)
-                    .min(nearest_region.y() + nearest_region.height() - 4.0),
+                    .max(nearest_region.y() - new_region.height() + options.snap_threshold)
+                    .min(nearest_region.y() + nearest_region.height() - options.snap_threshold),
             );
         }
 
@@ -72,36 +108,75 @@ pub fn display<R: Rectangular>(new_region: &mut R, other_displays: impl Iterator
             new_region.set_x(
                 new_region
                     .x()
-                    .max(nearest_region.x() - new_region.width() + 4.0)
-                    .min(nearest_region.x() + nearest_region.width() - 4.0),
+                    .max(nearest_region.x() - new_region.width() + options.snap_threshold)
+                    .min(nearest_region.x() + nearest_region.width() - options.snap_threshold),
             );
         }
     }
 
     // Snap-align on x-axis when alignment is near.
-    if (new_region.x() - nearest_region.x()).abs() <= 4.0 {
+    if (new_region.x() - nearest_region.x()).abs() <= options.snap_threshold {
         new_region.set_x(nearest_region.x());
     }
 
     // Snap-align on x-axis when alignment is near bottom edge.
     if ((new_region.x() + new_region.width()) - (nearest_region.x() + nearest_region.width())).abs()
-        <= 4.0
+        <= options.snap_threshold
     {
         new_region.set_x(nearest_region.x() + nearest_region.width() - new_region.width());
     }
 
     // Snap-align on y-axis when alignment is near.
-    if (new_region.y() - nearest_region.y()).abs() <= 4.0 {
+    if (new_region.y() - nearest_region.y()).abs() <= options.snap_threshold {
         new_region.set_y(nearest_region.y());
     }
 
     // Snap-align on y-axis when alignment is near bottom edge.
     if ((new_region.y() + new_region.height()) - (nearest_region.y() + nearest_region.height()))
         .abs()
-        <= 4.0
+        <= options.snap_threshold
     {
         new_region.set_y(nearest_region.y() + nearest_region.height() - new_region.height());
     }
+
+    push_out_of_overlaps(new_region, &other_displays);
+}
+
+/// Moves the region out of every display it overlaps, along the axis that overlaps
+/// least, so that it is left flush against the display it was pushed away from.
+///
+/// Pushing the region out of one display may push it into another, so this repeats
+/// until nothing overlaps, giving up after a bounded number of moves.
+fn push_out_of_overlaps<R: Rectangular>(region: &mut R, other_displays: &[R]) {
+    for _ in 0..other_displays.len() * 4 {
+        let Some(((overlap_x, overlap_y), other)) = other_displays
+            .iter()
+            .find_map(|other| Some((overlap(region, other)?, other)))
+        else {
+            return;
+        };
+
+        if overlap_x <= overlap_y {
+            if region.center_x() < other.center_x() {
+                region.set_x(other.x() - region.width());
+            } else {
+                region.set_x(other.x() + other.width());
+            }
+        } else if region.center_y() < other.center_y() {
+            region.set_y(other.y() - region.height());
+        } else {
+            region.set_y(other.y() + other.height());
+        }
+    }
+}
+
+/// Width and height of the area that two regions share, if they overlap.
+///
+/// Regions that only touch along an edge do not overlap.
+pub fn overlap<A: Rectangular, B: Rectangular>(a: &A, b: &B) -> Option<(f32, f32)> {
+    let width = (a.x() + a.width()).min(b.x() + b.width()) - a.x().max(b.x());
+    let height = (a.y() + a.height()).min(b.y() + b.height()) - a.y().max(b.y());
+    (width > 0.0 && height > 0.0).then_some((width, height))
 }
 
 fn distance(a: Point, b: Point) -> f32 {
@@ -122,7 +197,7 @@ pub enum NearestSide {
     West,
 }
 
-#[derive(Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
 pub struct Rectangle {
     pub x: f32,
     pub y: f32,
@@ -224,3 +299,68 @@ pub trait Rectangular: Default + Sized {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: f32, y: f32, width: f32, height: f32) -> Rectangle {
+        Rectangle {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn overlap_excludes_touching_edges() {
+        let a = rect(0.0, 0.0, 1920.0, 1080.0);
+        assert_eq!(overlap(&a, &rect(1920.0, 0.0, 1920.0, 1080.0)), None);
+        assert_eq!(overlap(&a, &rect(0.0, 1080.0, 1920.0, 1080.0)), None);
+        assert_eq!(
+            overlap(&a, &rect(1820.0, 1000.0, 1920.0, 1080.0)),
+            Some((100.0, 80.0))
+        );
+    }
+
+    #[test]
+    fn display_pushed_out_of_every_overlap() {
+        // Three displays in an L, and a fourth dropped across all of them.
+        let others = [
+            rect(0.0, 0.0, 1920.0, 1080.0),
+            rect(1920.0, 0.0, 1920.0, 1080.0),
+            rect(0.0, 1080.0, 1920.0, 1080.0),
+        ];
+
+        let mut region = rect(1000.0, 1000.0, 1920.0, 1080.0);
+        display(&mut region, others.iter().copied(), AlignOptions::default());
+
+        assert_eq!(region, rect(1920.0, 1080.0, 1920.0, 1080.0));
+        assert!(others.iter().all(|other| overlap(&region, other).is_none()));
+    }
+
+    #[test]
+    fn snap_within_threshold() {
+        let other = rect(0.0, 0.0, 1920.0, 1080.0);
+
+        let mut region = rect(1930.0, 3.0, 1920.0, 1080.0);
+        display(&mut region, std::iter::once(other), AlignOptions::default());
+        assert_eq!(region, rect(1920.0, 0.0, 1920.0, 1080.0));
+
+        let options = AlignOptions {
+            snap_threshold: 2.0,
+        };
+        let mut region = rect(1930.0, 3.0, 1920.0, 1080.0);
+        display(&mut region, std::iter::once(other), options);
+        assert_eq!(region, rect(1920.0, 3.0, 1920.0, 1080.0));
+    }
+
+    #[test]
+    fn snap_threshold_for_scale() {
+        assert_eq!(AlignOptions::for_scale(1.0), AlignOptions::default());
+        assert_eq!(AlignOptions::for_scale(2.0).snap_threshold, 2.0);
+        // Scales below 1 would otherwise snap from further than the default.
+        assert_eq!(AlignOptions::for_scale(0.5), AlignOptions::default());
+    }
+}
@@ -0,0 +1,127 @@
+// Copyright 2023 System76 <info@system76.com>
+// SPDX-License-Identifier: MPL-2.0
+
+//! Configurations remembered with `remember` and applied by `daemon`.
+
+use crate::error::CliError;
+use crate::outputs::{connected_outputs, resolve_output};
+use crate::preset::ModePreset;
+use crate::{store, App};
+use cosmic_randr_shell::{AdaptiveSyncState, Transform};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Configuration applied by the daemon whenever an output is connected.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct RememberedOutput {
+    #[serde(flatten)]
+    mode: ModePreset,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    position: Option<(i32, i32)>,
+}
+
+/// Remembered configurations, keyed by the serial number of the output.
+type RememberedOutputs = BTreeMap<String, RememberedOutput>;
+
+const REMEMBERED_OUTPUTS_FILE: &str = "remembered-outputs.json";
+
+impl App {
+    pub async fn remember(&mut self, output: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.dispatch_until_manager_done().await?;
+        let output = resolve_output(&self.context, output)?;
+
+        let head = self
+            .context
+            .output_heads
+            .values()
+            .find(|head| head.name == output)
+            .ok_or_else(|| CliError::UnknownOutput(output.to_string()))?;
+
+        if head.serial_number.is_empty() {
+            return Err(format!(
+                "{output} reports no serial number, so it cannot be recognized when connected"
+            )
+            .into());
+        }
+
+        let mode = head
+            .current()
+            .ok_or_else(|| format!("{output} has no current mode"))?;
+
+        let remembered = RememberedOutput {
+            mode: ModePreset {
+                width: mode.width,
+                height: mode.height,
+                refresh: Some(mode.refresh as f32 / 1000.0),
+                adaptive_sync: head
+                    .adaptive_sync
+                    .and_then(|sync| AdaptiveSyncState::try_from(sync).ok()),
+                scale: Some(head.scale),
+                transform: head
+                    .transform
+                    .and_then(|transform| Transform::try_from(transform).ok()),
+            },
+            position: Some((head.position_x, head.position_y)),
+        };
+
+        let mut outputs = store::load::<RememberedOutputs>(REMEMBERED_OUTPUTS_FILE)?;
+        outputs.insert(head.serial_number.clone(), remembered);
+        store::save(REMEMBERED_OUTPUTS_FILE, &outputs)?;
+
+        println!(
+            "remembered {output} by serial number {}",
+            head.serial_number
+        );
+        Ok(())
+    }
+
+    /// Applies the remembered configuration of each output that gets connected, once the
+    /// outputs have not changed for the `debounce` window.
+    pub async fn daemon(&mut self, debounce: Duration) -> Result<(), Box<dyn std::error::Error>> {
+        self.dispatch_until_manager_done().await?;
+        let mut connected = connected_outputs(&self.context);
+
+        loop {
+            self.wait_for_manager_done().await?;
+            self.settle(debounce).await?;
+
+            let previous = std::mem::replace(&mut connected, connected_outputs(&self.context));
+            let added = connected.difference(&previous).cloned().collect::<Vec<_>>();
+
+            if added.is_empty() {
+                continue;
+            }
+
+            // Reloaded on every hotplug so that `remember` applies without a restart.
+            let remembered = store::load::<RememberedOutputs>(REMEMBERED_OUTPUTS_FILE)?;
+
+            for name in added {
+                let Some(serial) = self
+                    .context
+                    .output_heads
+                    .values()
+                    .find(|head| head.name == name)
+                    .map(|head| head.serial_number.clone())
+                else {
+                    continue;
+                };
+
+                let Some(output) = remembered.get(&serial).filter(|_| !serial.is_empty()) else {
+                    continue;
+                };
+
+                let mut head_config = output.mode.to_head_config();
+                head_config.pos = output.position;
+
+                // A failure for one output must not stop the daemon.
+                match self.apply_head_config(&name, head_config).await {
+                    Ok(()) => println!("applied remembered configuration to {name}"),
+                    Err(why) => {
+                        eprintln!("failed to apply remembered configuration to {name}: {why}")
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,323 @@
+// Copyright 2023 System76 <info@system76.com>
+// SPDX-License-Identifier: MPL-2.0
+
+//! Enabling, disabling and mirroring outputs.
+
+use crate::error::CliError;
+use crate::geometry::{logical_rectangle, mode_logical_size};
+use crate::outputs::{context_has_output, resolve_output, sorted_heads};
+use crate::{submit, test_passed, App};
+use cosmic_randr::context::HeadConfiguration;
+use cosmic_randr::Context;
+use std::time::Duration;
+
+/// Time allowed for a newly connected output to advertise its modes.
+const MODES_TIMEOUT: Duration = Duration::from_millis(500);
+
+impl App {
+    pub async fn enable(
+        &mut self,
+        output: &str,
+        refresh: Option<f32>,
+        test: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.dispatch_until_manager_done().await?;
+        let output = resolve_output(&self.context, output)?;
+        self.wait_for_modes(&output).await?;
+        enable(&mut self.context, &output, refresh, test)?;
+        self.receive_config_messages().await?;
+        test_passed(test);
+
+        Ok(())
+    }
+
+    /// Gives an output that was just connected a moment to advertise its modes.
+    async fn wait_for_modes(&mut self, output: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let has_modes = |context: &Context| {
+            context
+                .output_heads
+                .values()
+                .any(|head| head.name == output && !head.modes.is_empty())
+        };
+
+        if !context_has_output(&self.context, output) || has_modes(&self.context) {
+            return Ok(());
+        }
+
+        let wait = async {
+            while !has_modes(&self.context) {
+                self.context.dispatch(&mut self.event_queue).await?;
+            }
+            Ok::<_, cosmic_randr::Error>(())
+        };
+
+        if let Ok(result) = tokio::time::timeout(MODES_TIMEOUT, wait).await {
+            result?;
+        }
+
+        if has_modes(&self.context) {
+            Ok(())
+        } else {
+            Err(format!("{output} reports no modes yet; try again in a moment").into())
+        }
+    }
+
+    /// Enables every disabled output at its preferred mode in a single configuration.
+    pub async fn enable_all(&mut self, test: bool) -> Result<(), Box<dyn std::error::Error>> {
+        self.dispatch_until_manager_done().await?;
+
+        // Place newly enabled outputs to the right of the current layout.
+        let mut x = self
+            .context
+            .output_heads
+            .values()
+            .filter(|head| head.enabled && head.mirroring.is_none())
+            .filter_map(|head| {
+                logical_rectangle(head).map(|rect| (rect.x + rect.width).round() as i32)
+            })
+            .max()
+            .unwrap_or(0);
+
+        let mut plan = Vec::new();
+
+        for head in sorted_heads(&self.context) {
+            if head.enabled {
+                continue;
+            }
+
+            let Some(mode) = head.preferred() else {
+                eprintln!("skipping {}: output reports no modes", head.name);
+                continue;
+            };
+
+            let head_config = HeadConfiguration {
+                size: Some((mode.width as u32, mode.height as u32)),
+                refresh: Some(mode.refresh as f32 / 1000.0),
+                pos: Some((x, 0)),
+                ..Default::default()
+            };
+
+            x += mode_logical_size(head, mode).0.round() as i32;
+            plan.push((head.name.clone(), head_config));
+        }
+
+        if plan.is_empty() {
+            println!("every output is already enabled");
+            return Ok(());
+        }
+
+        let mut config = self.context.create_output_config()?;
+        let mut names = Vec::with_capacity(plan.len());
+        for (name, head_config) in plan {
+            config.enable_head(&name, Some(head_config))?;
+            names.push(name);
+        }
+
+        if test {
+            submit(config, true)?;
+            self.receive_config_messages().await?;
+            println!("would enable: {}", names.join(", "));
+            test_passed(test);
+            return Ok(());
+        }
+
+        submit(config, false)?;
+        self.receive_config_messages().await?;
+
+        // Close any gaps or overlaps left by the outputs that were just enabled.
+        for name in &names {
+            self.auto_correct_offsets(name, false).await?;
+        }
+
+        println!("enabled: {}", names.join(", "));
+        Ok(())
+    }
+
+    pub async fn mirror(
+        &mut self,
+        output: &str,
+        from: &str,
+        test: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.dispatch_until_manager_done().await?;
+        let output = resolve_output(&self.context, output)?;
+        let from = resolve_output(&self.context, from)?;
+        mirror(&mut self.context, &output, &from, test)?;
+        self.receive_config_messages().await?;
+        test_passed(test);
+        Ok(())
+    }
+
+    /// Mirrors `from` on every other enabled output that isn't mirroring, in a single
+    /// configuration.
+    pub async fn mirror_all(
+        &mut self,
+        from: &str,
+        test: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.dispatch_until_manager_done().await?;
+        let from = resolve_output(&self.context, from)?;
+
+        let source = self
+            .context
+            .output_heads
+            .values()
+            .find(|head| head.name == from)
+            .ok_or_else(|| CliError::UnknownOutput(from.clone()))?;
+
+        if !source.enabled {
+            return Err(format!("{from} is disabled").into());
+        }
+
+        if let Some(mirrored) = source.mirroring.as_deref() {
+            return Err(format!("{from} is itself mirroring {mirrored}").into());
+        }
+
+        // Outputs that already mirror are left as they are, even from another output.
+        let targets = sorted_heads(&self.context)
+            .into_iter()
+            .filter(|head| head.enabled && head.mirroring.is_none() && head.name != from)
+            .map(|head| head.name.clone())
+            .collect::<Vec<_>>();
+
+        if targets.is_empty() {
+            println!("no other enabled output to mirror {from} on");
+            return Ok(());
+        }
+
+        let mut config = self.context.create_output_config()?;
+        for name in &targets {
+            config.mirror_head(name, &from, None)?;
+        }
+
+        submit(config, test)?;
+        self.receive_config_messages().await?;
+
+        let prefix = if test { "would be " } else { "" };
+        println!("{prefix}mirroring {from} on: {}", targets.join(", "));
+        test_passed(test);
+        Ok(())
+    }
+
+    pub async fn disable(
+        &mut self,
+        output: &str,
+        test: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.dispatch_until_manager_done().await?;
+        let output = resolve_output(&self.context, output)?;
+        disable(&mut self.context, &output, test)?;
+        self.receive_config_messages().await?;
+        test_passed(test);
+        Ok(())
+    }
+
+    /// Disables every output, except for `keep` when given, in a single configuration.
+    pub async fn disable_all(
+        &mut self,
+        keep: Option<&str>,
+        force: bool,
+        test: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.dispatch_until_manager_done().await?;
+
+        let keep = keep
+            .map(|output| resolve_output(&self.context, output))
+            .transpose()?;
+
+        let enable_kept = match keep.as_deref() {
+            Some(keep) => {
+                !self
+                    .context
+                    .output_heads
+                    .values()
+                    .find(|head| head.name == keep)
+                    .ok_or_else(|| CliError::UnknownOutput(keep.to_string()))?
+                    .enabled
+            }
+            None if force => false,
+            None => {
+                return Err(
+                    "disabling every output leaves no display enabled; pass --force to proceed"
+                        .into(),
+                )
+            }
+        };
+
+        let targets = sorted_heads(&self.context)
+            .into_iter()
+            .filter(|head| head.enabled && Some(head.name.as_str()) != keep.as_deref())
+            .map(|head| head.name.clone())
+            .collect::<Vec<_>>();
+
+        if targets.is_empty() && !enable_kept {
+            println!("no outputs to disable");
+            return Ok(());
+        }
+
+        let mut config = self.context.create_output_config()?;
+        for name in &targets {
+            config.disable_head(name)?;
+        }
+        if let Some(keep) = keep.as_deref().filter(|_| enable_kept) {
+            config.enable_head(keep, None)?;
+        }
+
+        submit(config, test)?;
+        self.receive_config_messages().await?;
+
+        let prefix = if test { "would be " } else { "" };
+        if let Some(keep) = keep.as_deref().filter(|_| enable_kept) {
+            println!("{prefix}enabled: {keep}");
+        }
+        println!("{prefix}disabled: {}", targets.join(", "));
+        test_passed(test);
+        Ok(())
+    }
+}
+
+fn disable(
+    context: &mut Context,
+    output: &str,
+    test: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut config = context.create_output_config()?;
+    config.disable_head(output)?;
+
+    submit(config, test)?;
+
+    Ok(())
+}
+
+fn enable(
+    context: &mut Context,
+    output: &str,
+    refresh: Option<f32>,
+    test: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let head_config = refresh.map(|refresh| HeadConfiguration {
+        refresh: Some(refresh),
+        ..Default::default()
+    });
+
+    let mut config = context.create_output_config()?;
+    config.enable_head(output, head_config)?;
+
+    submit(config, test)?;
+
+    Ok(())
+}
+
+fn mirror(
+    context: &mut Context,
+    output: &str,
+    from: &str,
+    test: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut config = context.create_output_config()?;
+    config.mirror_head(output, from, None)?;
+
+    submit(config, test)?;
+
+    Ok(())
+}
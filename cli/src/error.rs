@@ -0,0 +1,223 @@
+// Copyright 2023 System76 <info@system76.com>
+// SPDX-License-Identifier: MPL-2.0
+
+//! Errors that scripts can tell apart, and how they are reported.
+
+use cosmic_randr::context::{ConfigurationError, UnconfiguredHeads};
+use serde::Serialize;
+use std::error::Error;
+use std::fmt;
+use std::io::Write;
+use std::time::Duration;
+
+#[derive(Debug)]
+pub enum CliError {
+    /// The compositor rejected the configuration.
+    ConfigurationFailed,
+    /// The outputs changed before the configuration was applied.
+    ConfigurationCancelled,
+    /// The compositor does not support output management.
+    Unsupported,
+    /// The compositor did not respond to a configuration in time.
+    Timeout(Duration),
+    /// The compositor did not report the outputs in time.
+    NoResponse(Duration),
+    /// No connected output has this name.
+    UnknownOutput(String),
+    /// The output has no mode of the requested size and refresh rate, with the closest
+    /// that it supports instead.
+    ModeNotFound {
+        output: String,
+        alternatives: String,
+    },
+    /// The compositor stopped managing outputs, as it does while restarting.
+    ManagerFinished,
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ConfigurationFailed => f.write_str("configuration failed"),
+            Self::ConfigurationCancelled => f.write_str("configuration cancelled"),
+            Self::Unsupported => f.write_str(
+                "the compositor does not support wlr-output-management version 2 or later",
+            ),
+            Self::Timeout(timeout) => write!(
+                f,
+                "timed out after {} ms waiting for the configuration to apply",
+                timeout.as_millis()
+            ),
+            Self::NoResponse(timeout) => write!(
+                f,
+                "timed out after {} ms waiting for the compositor to report the outputs",
+                timeout.as_millis()
+            ),
+            Self::UnknownOutput(output) => write!(f, "unknown output: {output}"),
+            Self::ModeNotFound {
+                output,
+                alternatives,
+            } => write!(f, "no matching mode of {output}; {alternatives}"),
+            Self::ManagerFinished => f.write_str(
+                "the compositor stopped managing outputs, possibly because it is restarting",
+            ),
+        }
+    }
+}
+
+impl Error for CliError {}
+
+/// Category of an error, shared by the JSON error and the exit status.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    Failed,
+    Cancelled,
+    Unsupported,
+    Timeout,
+    UnknownOutput,
+    ManagerFinished,
+    InvalidConfiguration,
+    Other,
+}
+
+impl Kind {
+    #[must_use]
+    pub fn of(error: &(dyn Error + 'static)) -> Self {
+        if let Some(error) = error.downcast_ref::<CliError>() {
+            return match error {
+                CliError::ConfigurationFailed => Kind::Failed,
+                CliError::ConfigurationCancelled => Kind::Cancelled,
+                CliError::Unsupported => Kind::Unsupported,
+                CliError::Timeout(_) | CliError::NoResponse(_) => Kind::Timeout,
+                CliError::UnknownOutput(_) => Kind::UnknownOutput,
+                CliError::ModeNotFound { .. } => Kind::InvalidConfiguration,
+                CliError::ManagerFinished => Kind::ManagerFinished,
+            };
+        }
+
+        if let Some(error) = error.downcast_ref::<ConfigurationError>() {
+            return Self::of_configuration(*error);
+        }
+
+        if let Some(error) = error.downcast_ref::<UnconfiguredHeads>() {
+            return error
+                .failures
+                .first()
+                .map_or(Kind::InvalidConfiguration, |(_, why)| {
+                    Self::of_configuration(*why)
+                });
+        }
+
+        Kind::Other
+    }
+
+    /// Exit status of the process for errors of this kind.
+    #[must_use]
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Kind::Failed => 1,
+            Kind::Cancelled => 2,
+            Kind::Unsupported => 3,
+            Kind::Timeout => 4,
+            Kind::UnknownOutput => 5,
+            Kind::ManagerFinished => 6,
+            Kind::InvalidConfiguration => 7,
+            Kind::Other => 8,
+        }
+    }
+
+    fn of_configuration(error: ConfigurationError) -> Self {
+        match error {
+            ConfigurationError::UnknownOutput => Kind::UnknownOutput,
+            ConfigurationError::NoCosmicExtension
+            | ConfigurationError::UnsupportedVrrState
+            | ConfigurationError::NoCustomModes => Kind::Unsupported,
+            ConfigurationError::ManagerGone => Kind::ManagerFinished,
+            _ => Kind::InvalidConfiguration,
+        }
+    }
+}
+
+/// Name of the output that the error is about, if it concerns a single one.
+fn output(error: &(dyn Error + 'static)) -> Option<&str> {
+    if let Some(CliError::UnknownOutput(output) | CliError::ModeNotFound { output, .. }) =
+        error.downcast_ref::<CliError>()
+    {
+        return Some(output);
+    }
+
+    error
+        .downcast_ref::<UnconfiguredHeads>()
+        .filter(|error| error.failures.len() == 1)
+        .map(|error| error.failures[0].0.as_str())
+}
+
+#[derive(Serialize)]
+struct JsonError<'a> {
+    error: JsonErrorDetails<'a>,
+}
+
+#[derive(Serialize)]
+struct JsonErrorDetails<'a> {
+    kind: Kind,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output: Option<&'a str>,
+}
+
+/// Prints the error to stderr, as a versioned JSON object when `json` is set.
+pub fn report(error: &(dyn Error + 'static), json: bool) {
+    if !json {
+        eprintln!("error: {error}");
+        return;
+    }
+
+    let mut stderr = std::io::stderr().lock();
+    let _res = serde_json::to_writer(
+        &mut stderr,
+        &crate::json::Envelope {
+            version: crate::json::VERSION,
+            data: JsonError {
+                error: JsonErrorDetails {
+                    kind: Kind::of(error),
+                    message: error.to_string(),
+                    output: output(error),
+                },
+            },
+        },
+    );
+    let _res = stderr.write_all(b"\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KINDS: [Kind; 8] = [
+        Kind::Failed,
+        Kind::Cancelled,
+        Kind::Unsupported,
+        Kind::Timeout,
+        Kind::UnknownOutput,
+        Kind::ManagerFinished,
+        Kind::InvalidConfiguration,
+        Kind::Other,
+    ];
+
+    #[test]
+    fn configuration_outcomes() {
+        assert_eq!(Kind::of(&CliError::ConfigurationFailed).exit_code(), 1);
+        assert_eq!(Kind::of(&CliError::ConfigurationCancelled).exit_code(), 2);
+        assert_eq!(Kind::of(&CliError::Unsupported).exit_code(), 3);
+    }
+
+    #[test]
+    fn exit_codes_are_distinct() {
+        let mut codes = KINDS.map(Kind::exit_code).to_vec();
+        codes.extend([0, crate::EXIT_UNCHANGED, crate::EXIT_USAGE]);
+        codes.sort_unstable();
+        codes.dedup();
+
+        assert_eq!(codes.len(), KINDS.len() + 3);
+    }
+}
@@ -0,0 +1,288 @@
+// Copyright 2023 System76 <info@system76.com>
+// SPDX-License-Identifier: MPL-2.0
+
+//! Logical sizes and positions of the outputs, and notes about them.
+
+use crate::outputs::sorted_heads;
+use crate::{align, Mode};
+use cosmic_randr::output_head::OutputHead;
+use cosmic_randr::{Context, OutputMode};
+use cosmic_randr_shell::Transform;
+use std::collections::BTreeMap;
+use std::fmt::Write as FmtWrite;
+
+/// Explains the orientation a transform change results in, and warns when the request
+/// looks like it was meant to stack on top of the current transform.
+pub fn transform_note(head: &OutputHead, width: i32, height: i32, requested: Transform) {
+    let current = head
+        .transform
+        .and_then(|wl_transform| Transform::try_from(wl_transform).ok())
+        .unwrap_or(Transform::Normal);
+
+    if current != Transform::Normal && requested != Transform::Normal {
+        if current == requested {
+            eprintln!(
+                "note: {} is already {current}; transforms are absolute and do not stack",
+                head.name
+            );
+        } else {
+            eprintln!(
+                "note: {} is currently {current}; {requested} replaces it rather than stacking on top (stacked would be {})",
+                head.name,
+                current.compose(requested)
+            );
+        }
+    }
+
+    // Output management has no request for associating input devices with an output,
+    // so the transform of touchscreens and tablets can't be updated alongside it.
+    if current != requested {
+        eprintln!(
+            "note: touch and tablet input mapping is not rotated with {}; update it in the input settings if needed",
+            head.name
+        );
+    }
+
+    // Modes are advertised in the panel's native orientation.
+    if height > width {
+        eprintln!(
+            "note: {} is a portrait panel; with {requested} it will be {}",
+            head.name,
+            if requested.swaps_dimensions() {
+                "landscape"
+            } else {
+                "portrait"
+            }
+        );
+    }
+}
+
+/// Warns when the scale gives the output a fractional logical size, which some clients
+/// render poorly, and suggests the nearest scale that divides the mode evenly.
+pub fn fractional_scale_note(output: &str, width: i32, height: i32, scale: f64) {
+    let is_integer = |value: f64| (value - value.round()).abs() < 1e-6;
+
+    if is_integer(f64::from(width) / scale) && is_integer(f64::from(height) / scale) {
+        return;
+    }
+
+    let mut note = format!(
+        "note: scale {scale} gives {output} a fractional logical size of {:.2}x{:.2}",
+        f64::from(width) / scale,
+        f64::from(height) / scale
+    );
+
+    if let Some(even) = even_scale(width, height, scale) {
+        let _res = write!(
+            note,
+            "; scale {} gives {}x{}",
+            (even * 10_000.0).round() / 10_000.0,
+            (f64::from(width) / even).round() as i32,
+            (f64::from(height) / even).round() as i32
+        );
+    }
+
+    eprintln!("{note}");
+}
+
+/// Warns that X11 clients, which are unaware of the scale, are upscaled on the output.
+///
+/// Xwayland clients render at the unscaled resolution, so any scale but 100% leaves
+/// them blurry, however sharp native Wayland clients are.
+pub fn xwayland_scale_note(output: &str, scale: f64) {
+    if (scale - 1.0).abs() < 1e-6 {
+        return;
+    }
+
+    eprintln!(
+        "note: X11 applications running through Xwayland render at the unscaled resolution \
+         of {output}, and are upscaled {}% by the compositor, which may look blurry",
+        (scale * 100.0).round()
+    );
+}
+
+/// The scale nearest to `scale` for which both dimensions of the mode are a whole number
+/// of logical pixels.
+///
+/// Only scales representable by the fractional scale protocol, in steps of 1/120, are
+/// considered, as the compositor would round any other scale to one of those anyway.
+fn even_scale(width: i32, height: i32, scale: f64) -> Option<f64> {
+    const DENOMINATOR: u32 = 120;
+
+    let (mut a, mut b) = (width.unsigned_abs(), height.unsigned_abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+
+    // Scales of `gcd / n` are the only ones dividing both dimensions evenly, and `n` must
+    // divide `gcd * 120` for the scale to be a multiple of 1/120.
+    let steps = a.checked_mul(DENOMINATOR)?;
+    (1..=steps)
+        .filter(|n| steps % n == 0)
+        .map(|n| f64::from(a) / f64::from(n))
+        .min_by(|x, y| (x - scale).abs().total_cmp(&(y - scale).abs()))
+}
+
+/// Transform of the output, which is normal if unknown.
+pub fn head_transform(head: &OutputHead) -> Transform {
+    head.transform
+        .and_then(|wl_transform| Transform::try_from(wl_transform).ok())
+        .unwrap_or(Transform::Normal)
+}
+
+/// Size of the output's current mode in logical pixels, after its transform and scale.
+pub fn logical_size(head: &OutputHead) -> Option<(f32, f32)> {
+    let mode = head
+        .current_mode
+        .as_ref()
+        .and_then(|id| head.modes.get(id))?;
+    Some(mode_logical_size(head, mode))
+}
+
+/// Size of a mode of the output in logical pixels, after the output's transform and scale.
+pub fn mode_logical_size(head: &OutputHead, mode: &OutputMode) -> (f32, f32) {
+    let (width, height) = head_transform(head).apply_to_size((mode.width, mode.height));
+
+    (
+        width as f32 / head.scale as f32,
+        height as f32 / head.scale as f32,
+    )
+}
+
+/// Size in logical pixels that the mode gives the output, with the transform and scale
+/// it requests or else those of the output.
+pub fn requested_logical_size(head: &OutputHead, mode: &Mode) -> (f32, f32) {
+    let transform = mode.transform.unwrap_or_else(|| head_transform(head));
+    let scale = mode.scale.unwrap_or(head.scale) as f32;
+
+    transform.apply_to_size((mode.width as f32 / scale, mode.height as f32 / scale))
+}
+
+/// Fails if the output, placed at `x`, `y` with the given logical size, would overlap
+/// another enabled output, naming those it overlaps.
+pub fn check_overlap(
+    context: &Context,
+    output: &str,
+    x: i32,
+    y: i32,
+    width: f32,
+    height: f32,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let target = align::Rectangle {
+        x: x as f32,
+        y: y as f32,
+        width,
+        height,
+    };
+
+    let overlapped = sorted_heads(context)
+        .into_iter()
+        .filter(|head| head.name != output && head.enabled && head.mirroring.is_none())
+        .filter(|head| {
+            logical_rectangle(head).is_some_and(|other| align::overlap(&target, &other).is_some())
+        })
+        .map(|head| head.name.as_str())
+        .collect::<Vec<_>>();
+
+    if overlapped.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{output} at {x},{y} would overlap {}; pass --allow-overlap to apply it anyway",
+            overlapped.join(", ")
+        )
+        .into())
+    }
+}
+
+/// Position at which the output keeps its current center with the size the mode gives it.
+///
+/// Mirrored and disabled outputs have no position to keep.
+pub fn centered_position(context: &Context, mode: &Mode) -> Option<(i32, i32)> {
+    let head = context
+        .output_heads
+        .values()
+        .find(|head| head.name == mode.output && head.mirroring.is_none())?;
+    let old = logical_rectangle(head).filter(|_| head.enabled)?;
+
+    Some(recentered(&old, requested_logical_size(head, mode)))
+}
+
+/// Position at which a region of the given size shares the center of `old`.
+fn recentered(old: &align::Rectangle, (width, height): (f32, f32)) -> (i32, i32) {
+    (
+        (old.x + (old.width - width) / 2.0).round() as i32,
+        (old.y + (old.height - height) / 2.0).round() as i32,
+    )
+}
+
+/// Rectangles of the enabled outputs, keyed by output name.
+pub fn layout(context: &Context) -> BTreeMap<String, align::Rectangle> {
+    context
+        .output_heads
+        .values()
+        .filter(|head| head.enabled)
+        .filter_map(|head| Some((head.name.clone(), logical_rectangle(head)?)))
+        .collect()
+}
+
+/// Position and logical size of the output within the global compositor space.
+pub fn logical_rectangle(head: &OutputHead) -> Option<align::Rectangle> {
+    let (width, height) = logical_size(head)?;
+
+    Some(align::Rectangle {
+        x: head.position_x as f32,
+        y: head.position_y as f32,
+        width,
+        height,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recentered_on_smaller_and_larger_size() {
+        let old = align::Rectangle {
+            x: 1920.0,
+            y: 0.0,
+            width: 2560.0,
+            height: 1440.0,
+        };
+
+        assert_eq!(recentered(&old, (1920.0, 1080.0)), (2240, 180));
+        assert_eq!(recentered(&old, (3840.0, 2160.0)), (1280, -360));
+        assert_eq!(recentered(&old, (2560.0, 1440.0)), (1920, 0));
+    }
+
+    #[test]
+    fn even_scale_nearest() {
+        // 1600x900, where 1.5 gives 1706.67x960.
+        assert_eq!(even_scale(2560, 1440, 1.5), Some(1.6));
+        // 1600x1000, where 1.75 gives 1645.71x1028.57.
+        assert_eq!(even_scale(2880, 1800, 1.75), Some(1.8));
+    }
+
+    #[test]
+    fn even_scale_kept() {
+        assert_eq!(even_scale(3840, 2160, 1.5), Some(1.5));
+        assert_eq!(even_scale(1920, 1080, 1.25), Some(1.25));
+    }
+
+    #[test]
+    fn requested_logical_size_with_scale_and_transform() {
+        let mut head = OutputHead::inert("DP-1");
+        head.make = "Dell Inc.".to_owned();
+        head.model = "DELL U2723QE".to_owned();
+        head.scale = 1.25;
+
+        // The output's own scale applies unless the mode requests one.
+        let mut mode = Mode::new(head.name.clone(), 2560, 1440);
+        assert_eq!(requested_logical_size(&head, &mode), (2048.0, 1152.0));
+
+        mode.scale = Some(2.0);
+        mode.transform = Some(Transform::Rotate90);
+        assert_eq!(requested_logical_size(&head, &mode), (720.0, 1280.0));
+    }
+}
@@ -0,0 +1,295 @@
+// Copyright 2023 System76 <info@system76.com>
+// SPDX-License-Identifier: MPL-2.0
+
+//! Versioned JSON output shared by every query command.
+
+use crate::align::Rectangle;
+use cosmic_randr::output_head::OutputHead;
+use cosmic_randr::Context;
+use cosmic_randr_shell::{AdaptiveSyncAvailability, AdaptiveSyncState, Capabilities, Transform};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::Write;
+use wayland_client::Proxy;
+
+/// Version of the JSON envelope. Bump on incompatible changes to any payload.
+pub const VERSION: u32 = 1;
+
+/// Wraps every JSON payload as `{"version":1,...}`.
+#[derive(Serialize)]
+pub struct Envelope<T: Serialize> {
+    pub version: u32,
+    #[serde(flatten)]
+    pub data: T,
+}
+
+/// Serializes a payload within the versioned envelope to stdout.
+///
+/// # Errors
+///
+/// Returns error if serialization or writing to stdout fails.
+pub fn print<T: Serialize>(data: T) -> Result<(), Box<dyn std::error::Error>> {
+    let mut stdout = std::io::stdout().lock();
+    serde_json::to_writer(
+        &mut stdout,
+        &Envelope {
+            version: VERSION,
+            data,
+        },
+    )?;
+    stdout.write_all(b"\n")?;
+    stdout.flush()?;
+    Ok(())
+}
+
+#[derive(Serialize)]
+pub struct Outputs {
+    pub outputs: Vec<Output>,
+}
+
+#[derive(Serialize)]
+pub struct Output {
+    pub name: String,
+    /// Identifier of the display that is stable across connectors.
+    pub id: String,
+    pub enabled: bool,
+    pub make: Option<String>,
+    pub model: String,
+    pub serial_number: Option<String>,
+    pub physical: Size,
+    pub position: Position,
+    pub scale: f64,
+    /// Logical width after scale and transform, while enabled.
+    pub effective_width: Option<i32>,
+    /// Logical height after scale and transform, while enabled.
+    pub effective_height: Option<i32>,
+    pub transform: Option<String>,
+    pub mirroring: Option<String>,
+    pub adaptive_sync: Option<String>,
+    pub adaptive_sync_support: Option<String>,
+    pub capabilities: Capabilities,
+    pub modes: Vec<Mode>,
+}
+
+#[derive(Serialize)]
+pub struct Mode {
+    pub width: i32,
+    pub height: i32,
+    /// Refresh rate in mHz, as reported by the compositor.
+    pub refresh: i32,
+    /// Refresh rate in Hz.
+    pub refresh_hz: f64,
+    /// Always false for disabled outputs, which have no current mode.
+    pub current: bool,
+    pub preferred: bool,
+}
+
+/// Modes of a single output, as printed by `modes`.
+#[derive(Serialize)]
+pub struct Modes {
+    pub name: String,
+    pub modes: Vec<Mode>,
+}
+
+/// Geometry of each output before and after a layout change, in logical pixels.
+#[derive(Serialize)]
+pub struct Remap {
+    pub outputs: Vec<OutputRemap>,
+}
+
+#[derive(Serialize)]
+pub struct OutputRemap {
+    pub name: String,
+    /// Absent if the output was disabled before the change.
+    pub before: Option<Rectangle>,
+    /// Absent if the output is disabled after the change.
+    pub after: Option<Rectangle>,
+}
+
+/// Logical rectangle of each enabled output, keyed by output name.
+#[derive(Serialize)]
+pub struct Geometry {
+    pub outputs: BTreeMap<String, Rectangle>,
+}
+
+#[derive(Serialize)]
+pub struct Size {
+    pub width: i32,
+    pub height: i32,
+}
+
+#[derive(Serialize)]
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Output {
+    pub fn new(context: &Context, head: &OutputHead) -> Self {
+        let effective = crate::logical_size(head).filter(|_| head.enabled);
+
+        Self {
+            name: head.name.clone(),
+            id: head.stable_id(),
+            enabled: head.enabled,
+            make: (!head.make.is_empty()).then(|| head.make.clone()),
+            model: head.model.clone(),
+            serial_number: (!head.serial_number.is_empty()).then(|| head.serial_number.clone()),
+            physical: Size {
+                width: head.physical_width,
+                height: head.physical_height,
+            },
+            position: Position {
+                x: head.position_x,
+                y: head.position_y,
+            },
+            scale: head.scale,
+            effective_width: effective.map(|(width, _)| width.round() as i32),
+            effective_height: effective.map(|(_, height)| height.round() as i32),
+            transform: head
+                .transform
+                .and_then(|wl_transform| Transform::try_from(wl_transform).ok())
+                .map(|transform| transform.to_string()),
+            mirroring: head.mirroring.clone(),
+            adaptive_sync: head
+                .adaptive_sync
+                .and_then(|sync| AdaptiveSyncState::try_from(sync).ok())
+                .map(|sync| sync.to_string()),
+            adaptive_sync_support: head
+                .adaptive_sync_support
+                .map(|available| AdaptiveSyncAvailability::from(available).to_string()),
+            capabilities: context.capabilities(head),
+            modes: Mode::of_head(head),
+        }
+    }
+}
+
+impl Mode {
+    /// Every mode of the output, in the order of its modes.
+    pub fn of_head(head: &OutputHead) -> Vec<Self> {
+        head.modes
+            .values()
+            .map(|mode| Mode {
+                width: mode.width,
+                height: mode.height,
+                refresh: mode.refresh,
+                refresh_hz: f64::from(mode.refresh) / 1000.0,
+                current: head.enabled && head.current_mode.as_ref() == Some(&mode.wlr_mode.id()),
+                preferred: mode.preferred,
+            })
+            .collect()
+    }
+}
+
+/// Diagnostic snapshot of everything the context knows, for bug reports.
+#[derive(Serialize)]
+pub struct State {
+    pub globals: Vec<Global>,
+    pub output_manager_serial: u32,
+    pub cosmic_extension: bool,
+    pub heads: Vec<HeadState>,
+}
+
+#[derive(Serialize)]
+pub struct Global {
+    pub interface: String,
+    pub id: String,
+    pub version: u32,
+}
+
+/// Every field of an output head, with protocol values in their debug form.
+#[derive(Serialize)]
+pub struct HeadState {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub make: String,
+    pub model: String,
+    pub serial_number: String,
+    pub enabled: bool,
+    pub physical: Size,
+    pub position: Position,
+    pub scale: f64,
+    pub transform: Option<String>,
+    pub current_mode: Option<String>,
+    pub mirroring: Option<String>,
+    pub adaptive_sync: Option<String>,
+    pub adaptive_sync_support: Option<String>,
+    pub modes: Vec<ModeState>,
+}
+
+#[derive(Serialize)]
+pub struct ModeState {
+    pub id: String,
+    pub width: i32,
+    pub height: i32,
+    pub refresh: i32,
+    pub preferred: bool,
+}
+
+impl From<&Context> for State {
+    fn from(context: &Context) -> Self {
+        let mut globals = Vec::new();
+
+        if let Some(manager) = context.output_manager.as_ref() {
+            globals.push(Global {
+                interface: manager.id().interface().name.to_owned(),
+                id: manager.id().to_string(),
+                version: manager.version(),
+            });
+        }
+
+        if let Some(manager) = context.cosmic_output_manager.as_ref() {
+            globals.push(Global {
+                interface: manager.id().interface().name.to_owned(),
+                id: manager.id().to_string(),
+                version: manager.version(),
+            });
+        }
+
+        Self {
+            globals,
+            output_manager_serial: context.output_manager_serial,
+            cosmic_extension: context.cosmic_output_manager.is_some(),
+            heads: crate::sorted_heads(context)
+                .into_iter()
+                .map(|head| HeadState {
+                    id: head.wlr_head.id().to_string(),
+                    name: head.name.clone(),
+                    description: head.description.clone(),
+                    make: head.make.clone(),
+                    model: head.model.clone(),
+                    serial_number: head.serial_number.clone(),
+                    enabled: head.enabled,
+                    physical: Size {
+                        width: head.physical_width,
+                        height: head.physical_height,
+                    },
+                    position: Position {
+                        x: head.position_x,
+                        y: head.position_y,
+                    },
+                    scale: head.scale,
+                    transform: head.transform.map(|transform| format!("{transform:?}")),
+                    current_mode: head.current_mode.as_ref().map(ToString::to_string),
+                    mirroring: head.mirroring.clone(),
+                    adaptive_sync: head.adaptive_sync.map(|sync| format!("{sync:?}")),
+                    adaptive_sync_support: head
+                        .adaptive_sync_support
+                        .map(|available| format!("{available:?}")),
+                    modes: head
+                        .modes
+                        .iter()
+                        .map(|(id, mode)| ModeState {
+                            id: id.to_string(),
+                            width: mode.width,
+                            height: mode.height,
+                            refresh: mode.refresh,
+                            preferred: mode.preferred,
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,686 @@
+// Copyright 2023 System76 <info@system76.com>
+// SPDX-License-Identifier: MPL-2.0
+
+//! Positions of the outputs, and layouts applied from or written to KDL.
+
+use crate::error::CliError;
+use crate::geometry::{check_overlap, layout, logical_rectangle, logical_size};
+use crate::mode::{live_mode, mode_alternatives};
+use crate::outputs::{context_has_output, resolve_output, sorted_heads};
+use crate::parse::env_prefix;
+use crate::{align, json, submit, validate, App, ModeSort, Outcome, Side, Units};
+use cosmic_randr::context::HeadConfiguration;
+use cosmic_randr::{AdaptiveSyncStateExt, Context};
+use kdl::KdlDocument;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::{IsTerminal, Read, Write};
+use std::path::Path;
+use wayland_client::protocol::wl_output::Transform as WlTransform;
+
+/// Environment variable holding the layout for `kdl`, when neither a file nor stdin
+/// provides one.
+const LAYOUT_ENV: &str = "COSMIC_RANDR_LAYOUT";
+
+impl App {
+    /// Places the outputs in a row to the right of the first, with a gap between each.
+    pub async fn arrange(
+        &mut self,
+        outputs: &[String],
+        gap: u32,
+        test: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.dispatch_until_manager_done().await?;
+
+        let mut names = Vec::with_capacity(outputs.len());
+        for output in outputs {
+            let output = resolve_output(&self.context, output)?;
+            if names.contains(&output) {
+                return Err(format!("{output} is listed more than once").into());
+            }
+            names.push(output);
+        }
+
+        let mut placed: Vec<(&str, align::Rectangle)> = Vec::with_capacity(names.len());
+
+        for name in &names {
+            let head = self
+                .context
+                .output_heads
+                .values()
+                .find(|head| head.name == *name)
+                .ok_or_else(|| CliError::UnknownOutput(name.clone()))?;
+
+            if !head.enabled || head.mirroring.is_some() {
+                return Err(format!("{name} is not enabled as an extended display").into());
+            }
+
+            let mut rectangle =
+                logical_rectangle(head).ok_or_else(|| format!("{name} has no current mode"))?;
+
+            if let Some((_, previous)) = placed.last() {
+                rectangle.x = previous.x + previous.width + gap as f32;
+                rectangle.y = previous.y;
+            }
+
+            placed.push((name, rectangle));
+        }
+
+        let mut config = self.context.create_output_config()?;
+        for (name, rectangle) in &placed {
+            config.enable_head(
+                name,
+                Some(HeadConfiguration {
+                    pos: Some((rectangle.x.round() as i32, rectangle.y.round() as i32)),
+                    ..Default::default()
+                }),
+            )?;
+        }
+
+        submit(config, test)?;
+
+        self.receive_config_messages().await
+    }
+
+    /// Places each enabled output next to those before it, starting from the origin.
+    pub async fn auto_arrange(&mut self, test: bool) -> Result<(), Box<dyn std::error::Error>> {
+        self.dispatch_until_manager_done().await?;
+
+        let mut placed: Vec<(String, align::Rectangle)> = Vec::new();
+
+        for head in sorted_heads(&self.context) {
+            if !head.enabled || head.mirroring.is_some() {
+                continue;
+            }
+
+            let Some(mut rectangle) = logical_rectangle(head) else {
+                continue;
+            };
+
+            // Start to the right of the outputs placed so far, then attach to the nearest.
+            rectangle.x = placed
+                .iter()
+                .map(|(_, other)| other.x + other.width)
+                .fold(0.0, f32::max);
+            rectangle.y = 0.0;
+
+            if !placed.is_empty() {
+                align::display(
+                    &mut rectangle,
+                    placed.iter().map(|(_, other)| *other),
+                    align::AlignOptions::default(),
+                );
+            }
+
+            placed.push((head.name.clone(), rectangle));
+        }
+
+        if placed.is_empty() {
+            println!("no enabled outputs to arrange");
+            return Ok(());
+        }
+
+        let mut config = self.context.create_output_config()?;
+        for (name, rectangle) in &placed {
+            config.enable_head(
+                name,
+                Some(HeadConfiguration {
+                    pos: Some((rectangle.x.round() as i32, rectangle.y.round() as i32)),
+                    ..Default::default()
+                }),
+            )?;
+        }
+
+        submit(config, test)?;
+
+        self.receive_config_messages().await
+    }
+
+    /// Configures every output of the list that is connected in a single configuration,
+    /// or only those in `only` if any.
+    pub async fn apply_list(
+        &mut self,
+        list: &cosmic_randr_shell::List,
+        only: &[String],
+        test: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.dispatch_until_manager_done().await?;
+        self.submit_list(list, only, test).await
+    }
+
+    /// Same as [`Self::apply_list`], for callers that already dispatched the current
+    /// state of the outputs.
+    pub async fn submit_list(
+        &mut self,
+        list: &cosmic_randr_shell::List,
+        only: &[String],
+        test: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let outputs = selected_outputs(list, only)?;
+        let mut config = self.context.create_output_config()?;
+        let mut unmatched = Vec::new();
+
+        for output in outputs {
+            // Connector names are unique, while the make may be missing from the layout
+            // or reported differently by the compositor, so it only warrants a warning.
+            let Some(head) = self
+                .context
+                .output_heads
+                .values()
+                .find(|head| head.name == output.name)
+            else {
+                unmatched.push(output.name.as_str());
+                continue;
+            };
+
+            if head.model != output.model
+                || output.make.as_deref().is_some_and(|make| make != head.make)
+            {
+                eprintln!(
+                    "warning: {} is now {} {} rather than {} {}",
+                    output.name,
+                    head.make,
+                    head.model,
+                    output.make.as_deref().unwrap_or_default(),
+                    output.model
+                );
+            }
+
+            if !output.enabled {
+                config.disable_head(&output.name)?;
+                continue;
+            }
+
+            let mut head_config = HeadConfiguration {
+                scale: Some(output.scale),
+                transform: output.transform.map(WlTransform::from),
+                adaptive_sync: output.adaptive_sync.map(AdaptiveSyncStateExt::from),
+                ..Default::default()
+            };
+
+            if let Some(mode) = list.current_mode(output) {
+                let live =
+                    live_mode(head.modes.values(), mode).ok_or_else(|| CliError::ModeNotFound {
+                        output: output.name.clone(),
+                        alternatives: mode_alternatives(
+                            head.modes.values(),
+                            mode.size.0 as i32,
+                            mode.size.1 as i32,
+                        ),
+                    })?;
+
+                head_config.size = Some((live.width as u32, live.height as u32));
+                head_config.refresh = Some(live.refresh as f32 / 1000.0);
+            }
+
+            if let Some(from) = output.mirroring.as_deref() {
+                config.mirror_head(&output.name, from, Some(head_config))?;
+            } else {
+                head_config.pos = Some(output.position);
+                config.enable_head(&output.name, Some(head_config))?;
+            }
+        }
+
+        if !unmatched.is_empty() {
+            eprintln!(
+                "warning: not connected, so left out of the layout: {}",
+                unmatched.join(", ")
+            );
+        }
+
+        // Every head must be part of a configuration, so the others are sent again as
+        // they are, which the compositor should not treat as a change.
+        if !only.is_empty() && self.verbose {
+            let kept = sorted_heads(&self.context)
+                .into_iter()
+                .filter(|head| !only.contains(&head.name))
+                .map(|head| head.name.as_str())
+                .collect::<Vec<_>>();
+
+            if !kept.is_empty() {
+                eprintln!("note: re-sent at their current state: {}", kept.join(", "));
+            }
+        }
+
+        submit(config, test)?;
+
+        self.receive_config_messages().await
+    }
+
+    /// Writes the current layout as KDL, unless `only_if_changed` is set and the file
+    /// already describes it.
+    pub async fn snapshot(
+        &mut self,
+        path: Option<&Path>,
+        only_if_changed: bool,
+    ) -> Result<Outcome, Box<dyn std::error::Error>> {
+        self.dispatch_until_manager_done().await?;
+        self.sort_modes(ModeSort::Res, false);
+
+        let list = self.context.current_config();
+
+        let Some(path) = path else {
+            let mut stdout = std::io::stdout().lock();
+            write!(stdout, "{}", KdlDocument::from(list))?;
+            stdout.flush()?;
+            return Ok(Outcome::Done);
+        };
+
+        if only_if_changed && read_snapshot(path)?.is_some_and(|previous| previous == list) {
+            eprintln!("unchanged, not written: {}", path.display());
+            return Ok(Outcome::Unchanged);
+        }
+
+        std::fs::write(path, KdlDocument::from(list).to_string())
+            .map_err(|why| format!("failed to write {}: {why}", path.display()))?;
+
+        Ok(Outcome::Done)
+    }
+
+    pub async fn validate(
+        &mut self,
+        path: &Path,
+        json: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let document = std::fs::read_to_string(path)
+            .map_err(|why| format!("failed to read {}: {why}", path.display()))?
+            .parse::<KdlDocument>()?;
+
+        // Unknown nodes are reported here, as they are most likely typos.
+        let (list, mut problems) = match cosmic_randr_shell::List::from_kdl(document, true) {
+            Ok(list) => (list, Vec::new()),
+            Err(cosmic_randr_shell::KdlParseErrors { list, errors }) => {
+                let problems = errors
+                    .into_iter()
+                    .map(|why| validate::Problem {
+                        output: None,
+                        message: why.to_string(),
+                    })
+                    .collect();
+                (list, problems)
+            }
+        };
+
+        self.dispatch_until_manager_done().await?;
+        problems.extend(validate::check(&self.context, &list));
+
+        if json {
+            #[derive(Serialize)]
+            struct Problems<'a> {
+                problems: &'a [validate::Problem],
+            }
+
+            json::print(Problems {
+                problems: &problems,
+            })?;
+        } else {
+            for problem in &problems {
+                match problem.output.as_deref() {
+                    Some(output) => eprintln!("{output}: {}", problem.message),
+                    None => eprintln!("{}", problem.message),
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            if !json {
+                println!("{} can be applied", path.display());
+            }
+            Ok(())
+        } else {
+            Err(format!("{} problem(s) found in {}", problems.len(), path.display()).into())
+        }
+    }
+
+    /// Prints the logical rectangle of one output, or of every enabled output.
+    pub async fn geometry(
+        &mut self,
+        output: Option<&str>,
+        export: bool,
+        json: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.dispatch_until_manager_done().await?;
+
+        let mut rectangles = layout(&self.context);
+
+        if let Some(output) = output {
+            let output = resolve_output(&self.context, output)?;
+            if !context_has_output(&self.context, &output) {
+                return Err(CliError::UnknownOutput(output).into());
+            }
+
+            let rectangle = rectangles
+                .remove(&output)
+                .ok_or_else(|| format!("{output} is disabled"))?;
+            rectangles = BTreeMap::from([(output, rectangle)]);
+        }
+
+        if json {
+            return json::print(json::Geometry {
+                outputs: rectangles,
+            });
+        }
+
+        for (name, rectangle) in &rectangles {
+            let (x, y) = (rectangle.x.round() as i32, rectangle.y.round() as i32);
+            let (width, height) = (
+                rectangle.width.round() as i32,
+                rectangle.height.round() as i32,
+            );
+
+            if export {
+                let prefix = env_prefix(name);
+                println!("export {prefix}_X={x}");
+                println!("export {prefix}_Y={y}");
+                println!("export {prefix}_W={width}");
+                println!("export {prefix}_H={height}");
+            } else {
+                println!("{name}: {width}x{height} at {x},{y}");
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn set_position(
+        &mut self,
+        output: &str,
+        mut x: i32,
+        mut y: i32,
+        units: Units,
+        allow_overlap: bool,
+        test: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.dispatch_until_manager_done().await?;
+        let output = resolve_output(&self.context, output)?;
+
+        let head = self
+            .context
+            .output_heads
+            .values()
+            .find(|head| head.name == output)
+            .ok_or_else(|| CliError::UnknownOutput(output.to_string()))?;
+
+        if let Units::Device = units {
+            x = (f64::from(x) / head.scale).round() as i32;
+            y = (f64::from(y) / head.scale).round() as i32;
+        }
+
+        if let Some((width, height)) = logical_size(head).filter(|_| !allow_overlap) {
+            check_overlap(&self.context, &output, x, y, width, height)?;
+        }
+
+        set_position(&mut self.context, &output, x, y, test)?;
+        self.receive_config_messages().await?;
+        self.auto_correct_offsets(&output, test).await
+    }
+
+    /// Places the output next to `anchor`, then closes the gaps and moves the layout back
+    /// to the origin like an absolute position.
+    pub async fn place_beside(
+        &mut self,
+        output: &str,
+        side: Side,
+        anchor: &str,
+        test: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.dispatch_until_manager_done().await?;
+        let output = resolve_output(&self.context, output)?;
+        let anchor = resolve_output(&self.context, anchor)?;
+
+        if output == anchor {
+            return Err(format!("{output} cannot be placed beside itself").into());
+        }
+
+        // Logical sizes account for the scale and transform of each output.
+        let rectangle = |name: &str| -> Result<align::Rectangle, Box<dyn std::error::Error>> {
+            let head = self
+                .context
+                .output_heads
+                .values()
+                .find(|head| head.name == name)
+                .ok_or_else(|| CliError::UnknownOutput(name.to_string()))?;
+
+            if !head.enabled || head.mirroring.is_some() {
+                return Err(format!("{name} is not enabled as an extended display").into());
+            }
+
+            logical_rectangle(head).ok_or_else(|| format!("{name} has no current mode").into())
+        };
+
+        let target = rectangle(&output)?;
+        let anchor = rectangle(&anchor)?;
+
+        let (x, y) = match side {
+            Side::Left => (anchor.x - target.width, anchor.y),
+            Side::Right => (anchor.x + anchor.width, anchor.y),
+            Side::Above => (anchor.x, anchor.y - target.height),
+            Side::Below => (anchor.x, anchor.y + anchor.height),
+        };
+
+        set_position(
+            &mut self.context,
+            &output,
+            x.round() as i32,
+            y.round() as i32,
+            test,
+        )?;
+        self.receive_config_messages().await?;
+        self.auto_correct_offsets(&output, test).await
+    }
+
+    // Offset outputs in case of negative positioning.
+    pub async fn auto_correct_offsets(
+        &mut self,
+        output: &str,
+        test: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        // Get the position and dimensions of the moved display.
+        let Some((ref mut active_output, options)) = self
+            .context
+            .output_heads
+            .values()
+            .find(|head| head.name == output)
+            .and_then(|head| {
+                let options = align::AlignOptions::for_scale(head.scale);
+                logical_rectangle(head).map(|rectangle| (rectangle, options))
+            })
+        else {
+            return Ok(());
+        };
+
+        // Create an iterator of other outputs and their positions and dimensions.
+        let other_outputs = self.context.output_heads.values().filter_map(|head| {
+            if head.name == output || !head.enabled || head.mirroring.is_some() {
+                None
+            } else {
+                logical_rectangle(head)
+            }
+        });
+
+        // Align outputs such that there are no gaps.
+        align::display(active_output, other_outputs, options);
+
+        // Calculate how much to offset the position of each display to be aligned against (0,0)
+        let mut offset = self
+            .context
+            .output_heads
+            .values()
+            .filter(|head| head.enabled && head.mirroring.is_none())
+            .fold((i32::MAX, i32::MAX), |offset, head| {
+                let (x, y) = if output == head.name {
+                    (active_output.x as i32, active_output.y as i32)
+                } else {
+                    (head.position_x, head.position_y)
+                };
+
+                (offset.0.min(x), offset.1.min(y))
+            });
+
+        // Reposition each display with that offset
+        let updates = self
+            .context
+            .output_heads
+            .values()
+            .filter(|head| head.enabled && head.mirroring.is_none())
+            .map(|head| {
+                let (x, y) = if output == head.name {
+                    (active_output.x as i32, active_output.y as i32)
+                } else {
+                    (head.position_x, head.position_y)
+                };
+
+                (head.name.clone(), x - offset.0, y - offset.1)
+            })
+            .collect::<Vec<_>>();
+
+        // Adjust again to (0,0) baseline
+        offset = updates
+            .iter()
+            .fold((i32::MAX, i32::MAX), |offset, (_, x, y)| {
+                (offset.0.min(*x), offset.1.min(*y))
+            });
+
+        // Apply new positions
+        for (name, mut x, mut y) in updates {
+            x -= offset.0;
+            y -= offset.1;
+            set_position(&mut self.context, &name, x, y, test)?;
+            self.receive_config_messages().await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads the layout given to `kdl`, from the first of the file, stdin, and the
+/// environment that provides one.
+pub fn read_layout(path: Option<&Path>) -> Result<String, Box<dyn std::error::Error>> {
+    if let Some(path) = path {
+        return std::fs::read_to_string(path)
+            .map_err(|why| format!("failed to read {}: {why}", path.display()).into());
+    }
+
+    let mut stdin = std::io::stdin().lock();
+    if !stdin.is_terminal() {
+        let mut data = String::new();
+        stdin.read_to_string(&mut data)?;
+
+        // Containers often connect stdin to `/dev/null`, which provides nothing.
+        if !data.trim().is_empty() {
+            return Ok(data);
+        }
+    }
+
+    std::env::var(LAYOUT_ENV)
+        .ok()
+        .filter(|data| !data.trim().is_empty())
+        .ok_or_else(|| {
+            format!("no layout given: pass a file, pipe it to stdin, or set {LAYOUT_ENV}").into()
+        })
+}
+
+/// Parses a layout in the KDL format of `list --kdl`.
+pub fn parse_kdl_list(data: &str) -> Result<cosmic_randr_shell::List, Box<dyn std::error::Error>> {
+    cosmic_randr_shell::List::try_from(data.parse::<KdlDocument>()?).map_err(|why| {
+        for problem in &why.errors {
+            eprintln!("{problem}");
+        }
+
+        why.into()
+    })
+}
+
+/// Outputs of the layout to configure: every one of them, or only those in `only` if any.
+fn selected_outputs<'a>(
+    list: &'a cosmic_randr_shell::List,
+    only: &[String],
+) -> Result<Vec<&'a cosmic_randr_shell::Output>, String> {
+    if let Some(missing) = only
+        .iter()
+        .find(|name| !list.outputs.values().any(|output| output.name == **name))
+    {
+        return Err(format!("{missing} is not in the layout"));
+    }
+
+    Ok(list
+        .outputs
+        .values()
+        .filter(|output| only.is_empty() || only.contains(&output.name))
+        .collect())
+}
+
+/// Reads a previously written snapshot, if it exists and can be parsed.
+fn read_snapshot(
+    path: &Path,
+) -> Result<Option<cosmic_randr_shell::List>, Box<dyn std::error::Error>> {
+    let data = match std::fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(why) if why.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(why) => return Err(format!("failed to read {}: {why}", path.display()).into()),
+    };
+
+    // A snapshot that no longer parses is replaced rather than compared.
+    Ok(data
+        .parse::<KdlDocument>()
+        .ok()
+        .and_then(|document| cosmic_randr_shell::List::try_from(document).ok()))
+}
+
+fn set_position(
+    context: &mut Context,
+    name: &str,
+    x: i32,
+    y: i32,
+    test: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut config = context.create_output_config()?;
+    config.enable_head(
+        name,
+        Some(HeadConfiguration {
+            pos: Some((x, y)),
+            ..Default::default()
+        }),
+    )?;
+
+    submit(config, test)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selected_layout_outputs() {
+        let list = parse_kdl_list(
+            r#"
+output "DP-1" enabled=true {
+    position 0 0
+}
+output "DP-2" enabled=true {
+    position 2560 0
+}
+output "HDMI-A-1" enabled=false {
+    position 0 0
+}
+"#,
+        )
+        .unwrap();
+
+        let names = |only: &[&str]| {
+            let only = only.iter().map(|&name| name.to_owned()).collect::<Vec<_>>();
+            selected_outputs(&list, &only).map(|outputs| {
+                outputs
+                    .iter()
+                    .map(|output| output.name.clone())
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        assert_eq!(names(&[]).unwrap(), ["DP-1", "DP-2", "HDMI-A-1"]);
+        assert_eq!(names(&["HDMI-A-1", "DP-2"]).unwrap(), ["DP-2", "HDMI-A-1"]);
+        assert_eq!(names(&["DP-3"]).unwrap_err(), "DP-3 is not in the layout");
+    }
+}
@@ -0,0 +1,368 @@
+// Copyright 2023 System76 <info@system76.com>
+// SPDX-License-Identifier: MPL-2.0
+
+//! Rendering of the outputs for `list`.
+
+use crate::geometry::{logical_size, xwayland_scale_note};
+use crate::outputs::sorted_heads_by;
+use crate::{json, App, ListFormat, ModeSort, OutputSort};
+use cosmic_randr::output_head::OutputHead;
+use cosmic_randr::{AdaptiveSyncAvailability, AdaptiveSyncStateExt, Context};
+use cosmic_randr_shell::Transform;
+use kdl::KdlDocument;
+use nu_ansi_term::{Color, Style};
+use std::fmt::Write as FmtWrite;
+use std::io::Write;
+use std::time::Duration;
+use wayland_client::Proxy;
+
+fn list(context: &Context, sort: OutputSort, color: bool) {
+    print_text(&list_text(sorted_heads_by(context, sort)), color);
+}
+
+/// Prints text rendered for a terminal, without its colors unless `color` is set.
+pub fn print_text(text: &str, color: bool) {
+    let mut stdout = std::io::stdout().lock();
+
+    let _res = if color {
+        stdout.write_all(text.as_bytes())
+    } else {
+        stdout.write_all(strip_colors(text).as_bytes())
+    };
+
+    let _res = stdout.flush();
+}
+
+/// Renders the outputs as printed by `list`, with colors.
+pub fn list_text<'a>(heads: impl IntoIterator<Item = &'a OutputHead>) -> String {
+    let mut output = String::new();
+
+    for head in heads {
+        #[allow(clippy::ignored_unit_patterns)]
+        let _res = fomat_macros::witeln!(
+            &mut output,
+            (Style::new().bold().paint(&head.name)) " "
+            if head.enabled {
+                if let Some(from) = head.mirroring.as_ref() {
+                    (Color::Blue.bold().paint(format!("(mirroring \"{}\")", from)))
+                } else {
+                    (Color::Green.bold().paint("(enabled)"))
+                }
+            } else {
+                (Color::Red.bold().paint("(disabled)"))
+            }
+            if !head.make.is_empty() {
+                (Color::Yellow.bold().paint("\n  Make: ")) (head.make)
+            }
+            (Color::Yellow.bold().paint("\n  Model: "))
+            (head.model)
+            (Color::Yellow.bold().paint("\n  ID: "))
+            (head.stable_id())
+            (Color::Yellow.bold().paint("\n  Physical Size: "))
+            (head.physical_width) " x " (head.physical_height) " mm"
+            (Color::Yellow.bold().paint("\n  Position: "))
+            (head.position_x) "," (head.position_y)
+            (Color::Yellow.bold().paint("\n  Scale: ")) ((head.scale * 100.0) as i32) "%"
+            if head.enabled {
+                if let Some((width, height)) = logical_size(head) {
+                    (Color::Yellow.bold().paint("\n  Effective: "))
+                    (width.round() as i32) "x" (height.round() as i32)
+                }
+            }
+            if let Some(wl_transform) = head.transform {
+                if let Ok(transform) = Transform::try_from(wl_transform) {
+                    (Color::Yellow.bold().paint("\n  Transform: ")) (transform)
+                }
+            }
+            if let Some(available) = head.adaptive_sync_support {
+                (Color::Yellow.bold().paint("\n  Adaptive Sync Support: "))
+                (match available {
+                    AdaptiveSyncAvailability::Supported | AdaptiveSyncAvailability::RequiresModeset => Color::Green.paint("true"),
+                    _ => Color::Red.paint("false"),
+                })
+                if let Some((min, max)) = head.vrr_range() {
+                    (Color::Yellow.bold().paint("\n  VRR Range: "))
+                    (min / 1000) "." (format!("{:03}", min % 1000)) " - "
+                    (max / 1000) "." (format!("{:03}", max % 1000)) " Hz"
+                }
+            }
+            if let Some(sync) = head.adaptive_sync {
+                (Color::Yellow.bold().paint("\n  Adaptive Sync: "))
+                (match sync {
+                    AdaptiveSyncStateExt::Always => {
+                        Color::Green.paint("true\n")
+                    },
+                    AdaptiveSyncStateExt::Automatic => {
+                        Color::Green.paint("automatic\n")
+                    },
+                    _ => {
+                        Color::Red.paint("false\n")
+                    }
+                })
+            }
+            (Color::Yellow.bold().paint("\n  Modes:"))
+        );
+
+        write_modes(&mut output, head);
+    }
+
+    output
+}
+
+/// Writes a line for each mode of the output, marking the current and preferred modes.
+pub fn write_modes(output: &mut String, head: &OutputHead) {
+    let mut resolution = String::new();
+
+    // No protocol reports the effective refresh rate, which varies under adaptive
+    // sync, so the nominal rate of the current mode is marked as an upper bound.
+    let variable_refresh = matches!(
+        head.adaptive_sync,
+        Some(AdaptiveSyncStateExt::Always | AdaptiveSyncStateExt::Automatic)
+    );
+
+    for mode in head.modes.values() {
+        resolution.clear();
+        let _res = write!(&mut resolution, "{}x{}", mode.width, mode.height);
+
+        let _res = writeln!(
+            output,
+            "    {:>9} @ {}{}{}{}",
+            Color::Magenta.paint(format!("{resolution:>9}")),
+            Color::Cyan.paint(format!(
+                "{:>3}.{:03} Hz",
+                mode.refresh / 1000,
+                mode.refresh % 1000
+            )),
+            if head.current_mode.as_ref() == Some(&mode.wlr_mode.id()) {
+                Color::Purple.bold().paint(" (current)")
+            } else {
+                Color::default().paint("")
+            },
+            if variable_refresh && head.current_mode.as_ref() == Some(&mode.wlr_mode.id()) {
+                Color::Cyan.paint(" (maximum, VRR)")
+            } else {
+                Color::default().paint("")
+            },
+            if mode.preferred {
+                Color::Green.bold().paint(" (preferred)")
+            } else {
+                Color::default().paint("")
+            }
+        );
+    }
+}
+
+/// Removes the color escape sequences from text rendered for a terminal.
+pub fn strip_colors(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Skip the control sequence up to its final letter, such as `m`.
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            plain.push(c);
+        }
+    }
+
+    plain
+}
+
+/// Columns of `list --format csv`, which scripts may rely on.
+///
+/// No protocol reports a primary output, so `primary` is always empty.
+const CSV_HEADER: &str =
+    "name,make,model,serial,enabled,width,height,refresh_mhz,scale,x,y,transform,primary,id";
+
+fn list_csv(context: &Context, sort: OutputSort) -> std::io::Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    writeln!(stdout, "{CSV_HEADER}")?;
+
+    for head in sorted_heads_by(context, sort) {
+        let mode = head
+            .current_mode
+            .as_ref()
+            .and_then(|id| head.modes.get(id))
+            .filter(|_| head.enabled);
+
+        let (width, height, refresh) = mode
+            .map(|mode| {
+                (
+                    mode.width.to_string(),
+                    mode.height.to_string(),
+                    mode.refresh.to_string(),
+                )
+            })
+            .unwrap_or_default();
+
+        let transform = head
+            .transform
+            .and_then(|wl_transform| Transform::try_from(wl_transform).ok())
+            .map(|transform| transform.to_string())
+            .unwrap_or_default();
+
+        writeln!(
+            stdout,
+            "{},{},{},{},{},{width},{height},{refresh},{},{},{},{transform},,{}",
+            csv_field(&head.name),
+            csv_field(&head.make),
+            csv_field(&head.model),
+            csv_field(&head.serial_number),
+            head.enabled,
+            head.scale,
+            head.position_x,
+            head.position_y,
+            csv_field(&head.stable_id()),
+        )?;
+    }
+
+    stdout.flush()
+}
+
+/// Quotes a CSV field if it contains a separator, a quote or a line break.
+fn csv_field(value: &str) -> std::borrow::Cow<'_, str> {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\"")).into()
+    } else {
+        value.into()
+    }
+}
+
+pub fn list_kdl(context: &Context, sort: OutputSort, compact: bool) {
+    let mut document = KdlDocument::from(context.current_config());
+
+    // The outputs of the configuration are always in connector name order.
+    let names = sorted_heads_by(context, sort)
+        .into_iter()
+        .map(|head| head.name.as_str())
+        .collect::<Vec<_>>();
+    document.nodes_mut().sort_by_key(|node| {
+        node.get(0)
+            .and_then(|entry| entry.value().as_string())
+            .and_then(|name| names.iter().position(|other| *other == name))
+    });
+
+    if compact {
+        compact_kdl(&mut document);
+    }
+
+    let mut stdout = std::io::stdout().lock();
+    let _res = write!(stdout, "{document}");
+    let _res = stdout.flush();
+}
+
+/// Places each output of the document on a single line.
+pub fn compact_kdl(document: &mut KdlDocument) {
+    for node in document.nodes_mut() {
+        node.set_leading("");
+        node.set_trailing("\n");
+
+        if let Some(children) = node.children_mut() {
+            compact_kdl_children(children);
+        }
+    }
+}
+
+fn compact_kdl_children(document: &mut KdlDocument) {
+    document.set_leading("");
+    document.set_trailing("");
+
+    for (id, node) in document.nodes_mut().iter_mut().enumerate() {
+        node.set_leading(if id == 0 { "" } else { " " });
+        node.set_trailing(";");
+
+        if let Some(children) = node.children_mut() {
+            compact_kdl_children(children);
+        }
+    }
+}
+
+impl App {
+    #[allow(clippy::too_many_arguments)]
+    pub async fn list(
+        &mut self,
+        kdl: bool,
+        compact: bool,
+        format: Option<ListFormat>,
+        json: bool,
+        sort: ModeSort,
+        ascending: bool,
+        output_sort: OutputSort,
+        wait: Option<usize>,
+        settle: Duration,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.dispatch_until_manager_done().await?;
+        if let Some(min_outputs) = wait {
+            let timeout = self.timeout;
+            let connected = self
+                .context
+                .wait_for_outputs(&mut self.event_queue, min_outputs, timeout)
+                .await?;
+
+            if !connected {
+                return Err(format!(
+                    "only {} of {min_outputs} outputs were connected after {} ms",
+                    self.context.output_heads.len(),
+                    timeout.as_millis()
+                )
+                .into());
+            }
+        }
+        if !settle.is_zero() {
+            self.settle(settle).await?;
+        }
+        self.sort_modes(sort, ascending);
+
+        if let Some(ListFormat::Csv) = format {
+            list_csv(&self.context, output_sort)?;
+        } else if json {
+            json::print(json::Outputs {
+                outputs: sorted_heads_by(&self.context, output_sort)
+                    .into_iter()
+                    .map(|head| json::Output::new(&self.context, head))
+                    .collect(),
+            })?;
+        } else if kdl {
+            list_kdl(&self.context, output_sort, compact);
+        } else {
+            list(&self.context, output_sort, self.color);
+
+            if self.verbose {
+                for head in sorted_heads_by(&self.context, output_sort) {
+                    if head.enabled {
+                        xwayland_scale_note(&head.name, head.scale);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_field_quoting() {
+        assert_eq!(csv_field("DELL U2720Q"), "DELL U2720Q");
+        assert_eq!(csv_field("Dell Inc., Ltd."), "\"Dell Inc., Ltd.\"");
+        assert_eq!(csv_field("27\" panel"), "\"27\"\" panel\"");
+        assert_eq!(csv_field("line\nbreak"), "\"line\nbreak\"");
+    }
+
+    #[test]
+    fn strip_color_sequences() {
+        assert_eq!(
+            strip_colors("\x1b[1;32mDP-1\x1b[0m (enabled)"),
+            "DP-1 (enabled)"
+        );
+        assert_eq!(strip_colors("no colors"), "no colors");
+    }
+}
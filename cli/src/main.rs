@@ -2,65 +2,187 @@
 // SPDX-License-Identifier: MPL-2.0
 
 pub mod align;
-
-use clap::{Parser, ValueEnum};
-use cosmic_randr::context::HeadConfiguration;
+mod daemon;
+mod enable;
+pub mod error;
+mod geometry;
+pub mod json;
+mod layout;
+mod list;
+mod mode;
+mod outputs;
+mod parse;
+mod present;
+mod preset;
+mod report;
+pub mod store;
+pub mod validate;
+mod watch;
+
+use clap::{CommandFactory, Parser};
+use clap_complete::engine::ArgValueCandidates;
+use clap_complete::env::CompleteEnv;
+use cosmic_randr::context::{Configuration, HeadConfiguration, MissingRefresh, UnconfiguredHeads};
+use cosmic_randr::output_head::OutputHead;
 use cosmic_randr::Message;
-use cosmic_randr::{AdaptiveSyncAvailability, AdaptiveSyncStateExt, Context};
-use nu_ansi_term::{Color, Style};
-use std::fmt::{Display, Write as FmtWrite};
-use std::io::Write;
+use cosmic_randr::{AdaptiveSyncStateExt, Context, OutputMode};
+use cosmic_randr_shell::{AdaptiveSyncState, Transform};
+use error::CliError;
+use layout::{parse_kdl_list, read_layout};
+use outputs::{output_candidates, sorted_heads};
+use parse::{parse_duration, parse_mode_refresh, parse_refresh, parse_scale};
+use std::io::IsTerminal;
+use std::path::PathBuf;
+use std::time::Duration;
 use tachyonix::Receiver;
 use wayland_client::protocol::wl_output::Transform as WlTransform;
-use wayland_client::{EventQueue, Proxy};
+use wayland_client::EventQueue;
 
 /// Display and configure wayland outputs
 #[derive(clap::Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
+    /// Print query results as versioned JSON, and errors as JSON on stderr.
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Milliseconds to wait for the compositor to apply or test a configuration.
+    #[arg(long, global = true, value_name = "MS", default_value_t = 10_000)]
+    apply_timeout: u64,
+
+    /// Milliseconds to wait for the compositor to report the outputs.
+    #[arg(long, global = true, value_name = "MS", default_value_t = 5_000)]
+    timeout: u64,
+
+    /// Ignore the COSMIC extension, as if running on another wlroots compositor.
+    ///
+    /// A debugging aid for the fallbacks used without the extension, such as integer
+    /// scales and the lack of mirroring. Equivalent to `COSMIC_RANDR_NO_COSMIC=1`.
+    #[arg(long, global = true)]
+    no_cosmic: bool,
+
+    /// Print additional notes about the configuration being applied.
+    #[arg(long, short, global = true)]
+    verbose: bool,
+
+    /// Only test every configuration, as with `--test`, printing the changes that
+    /// would be applied.
+    #[arg(long, global = true)]
+    dry_run: bool,
+
+    /// Whether to color human-readable output. By default, colors are used when
+    /// stdout is a terminal and `NO_COLOR` is unset.
+    #[arg(long, global = true, value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+
     #[command(subcommand)]
     command: Commands,
 }
 
-#[derive(clap::Args, Debug)]
+#[derive(clap::Args, Clone, Debug)]
 struct Mode {
-    /// Name of the output that the display is connected to.
+    /// Name of the output that the display is connected to, or `@N` for the Nth output in `list`.
+    #[arg(add = ArgValueCandidates::new(output_candidates))]
     output: String,
     /// Specifies the height of the output picture.
     width: i32,
     /// Specifies the width of the output picture.
     height: i32,
-    /// Specifies the refresh rate to apply to the output.
-    #[arg(long)]
-    refresh: Option<f32>,
+    /// Refresh rate in Hz, such as 144 or 59.94, or `max` or `min` for the highest or
+    /// lowest refresh rate available at the resolution.
+    #[arg(
+        long,
+        visible_alias = "refresh-hz",
+        value_name = "HZ",
+        value_parser = parse_mode_refresh
+    )]
+    refresh: Option<Refresh>,
+    /// What to do when no mode of this size has the requested refresh rate.
+    #[arg(long, value_enum, default_value_t = OnMissingRefresh::Error, requires = "refresh")]
+    on_missing_refresh: OnMissingRefresh,
     /// Specfies the adaptive sync mode to apply to the output.
     #[arg(long, value_enum)]
-    adaptive_sync: Option<AdaptiveSync>,
-    /// Position the output within this x pixel coordinate.
+    adaptive_sync: Option<AdaptiveSyncState>,
+    /// Position the output within this x coordinate, in logical pixels.
     #[arg(long, allow_hyphen_values(true))]
     pos_x: Option<i32>,
-    /// Position the output within this y pixel coordinate.
+    /// Position the output within this y coordinate, in logical pixels.
     #[arg(long, allow_hyphen_values(true))]
     pos_y: Option<i32>,
-    /// Changes the dimensions of the output picture.
-    #[arg(long)]
+    /// Changes the dimensions of the output picture, as a factor such as `1.5` or a
+    /// percentage such as `150%`.
+    #[arg(long, value_parser = parse_scale)]
     scale: Option<f64>,
     /// Tests the output configuration without applying it.
     #[arg(long)]
     test: bool,
     /// Specifies a transformation matrix to apply to the output.
+    ///
+    /// Touch and tablet input mapping is configured separately from the output, and
+    /// is not rotated along with it.
     #[arg(long, value_enum)]
     transform: Option<Transform>,
+    /// After applying, print the old and new geometry of each output as JSON.
+    ///
+    /// Lets window management tools move windows along with the outputs they were on.
+    #[arg(long, conflicts_with = "test")]
+    emit_remap: bool,
+    /// Retry up to N times, with backoff, when the compositor rejects the adaptive sync
+    /// change, then apply the rest of the configuration without it.
+    ///
+    /// Drivers may briefly refuse adaptive sync right after a display is connected.
+    #[arg(long, value_name = "N", requires = "adaptive_sync")]
+    retry_vrr: Option<u32>,
+    /// Keep the center of the output in place when its size changes, as when rotating
+    /// or rescaling it, rather than its top left corner.
+    #[arg(long, conflicts_with_all = ["pos_x", "pos_y"])]
+    keep_center: bool,
+    /// Fail when adaptive sync can't be left to the compositor, rather than enabling it.
+    #[arg(long, requires = "adaptive_sync")]
+    strict: bool,
+    /// Apply a position given with `--pos-x` or `--pos-y` even if the output would then
+    /// overlap another.
+    #[arg(long)]
+    allow_overlap: bool,
 }
 
 impl Mode {
+    /// Requests `width`x`height` for the output, leaving everything else as it is.
+    fn new(output: String, width: i32, height: i32) -> Self {
+        Self {
+            output,
+            width,
+            height,
+            refresh: None,
+            on_missing_refresh: OnMissingRefresh::Error,
+            adaptive_sync: None,
+            pos_x: None,
+            pos_y: None,
+            scale: None,
+            test: false,
+            transform: None,
+            emit_remap: false,
+            retry_vrr: None,
+            keep_center: false,
+            strict: false,
+            allow_overlap: false,
+        }
+    }
+
+    /// Requests one of the output's modes, leaving everything else as it is.
+    fn for_head(head: &OutputHead, mode: &OutputMode) -> Self {
+        Self {
+            refresh: Some(Refresh::Hz(mode.refresh as f32 / 1000.0)),
+            ..Self::new(head.name.clone(), mode.width, mode.height)
+        }
+    }
+
     fn to_head_config(&self) -> HeadConfiguration {
         HeadConfiguration {
             size: Some((self.width as u32, self.height as u32)),
-            refresh: self.refresh,
-            adaptive_sync: self
-                .adaptive_sync
-                .map(|adaptive_sync| adaptive_sync.adaptive_sync_state_ext()),
+            refresh: self.refresh.and_then(Refresh::hz),
+            adaptive_sync: self.adaptive_sync.map(AdaptiveSyncStateExt::from),
+            adaptive_sync_fallback: !self.strict,
             pos: (self.pos_x.is_some() || self.pos_y.is_some()).then(|| {
                 (
                     self.pos_x.unwrap_or_default(),
@@ -68,189 +190,967 @@ impl Mode {
                 )
             }),
             scale: self.scale,
-            transform: self.transform.map(|transform| transform.wl_transform()),
+            transform: self.transform.map(WlTransform::from),
+            on_missing_refresh: self.on_missing_refresh.into(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Refresh rate given to `mode --refresh`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Refresh {
+    /// Refresh rate in Hz.
+    Hz(f32),
+    /// Highest refresh rate available at the resolution.
+    Max,
+    /// Lowest refresh rate available at the resolution.
+    Min,
+}
+
+impl Refresh {
+    fn hz(self) -> Option<f32> {
+        match self {
+            Self::Hz(hz) => Some(hz),
+            Self::Max | Self::Min => None,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum OnMissingRefresh {
+    /// Fail without changing the mode.
+    Error,
+    /// Use the closest refresh rate available at the resolution.
+    Nearest,
+    /// Use the refresh rate of the preferred mode.
+    Preferred,
+    /// Keep the current refresh rate, and only change the resolution.
+    Ignore,
+}
+
+impl From<OnMissingRefresh> for MissingRefresh {
+    fn from(policy: OnMissingRefresh) -> Self {
+        match policy {
+            OnMissingRefresh::Error => MissingRefresh::Error,
+            OnMissingRefresh::Nearest => MissingRefresh::Nearest,
+            OnMissingRefresh::Preferred => MissingRefresh::Preferred,
+            OnMissingRefresh::Ignore => MissingRefresh::Ignore,
         }
     }
 }
 
 #[derive(clap::Subcommand, Debug)]
 enum Commands {
+    /// Set a display to a mode that it does not advertise, such as a resolution that
+    /// the panel supports but that is missing from its EDID.
+    ///
+    /// The compositor decides whether to accept the mode, and may reject it even if the
+    /// panel would support it.
+    AddMode {
+        /// Name, serial number or model of the output, or `@N` for the Nth output in `list`.
+        #[arg(add = ArgValueCandidates::new(output_candidates))]
+        output: String,
+        /// Width of the mode, in pixels.
+        width: i32,
+        /// Height of the mode, in pixels.
+        height: i32,
+        /// Refresh rate in Hz, such as 60 or 59.94. The compositor chooses one if omitted.
+        #[arg(value_parser = parse_refresh)]
+        refresh: Option<f32>,
+        /// Test the mode without applying it.
+        #[arg(long)]
+        test: bool,
+    },
+
+    /// Place displays side by side from left to right, in the order given.
+    ///
+    /// The first display keeps its position, and the others follow to its right, aligned
+    /// to its top edge.
+    Arrange {
+        /// Names of the outputs, or `@N` for the Nth output in `list`.
+        #[arg(required = true, num_args = 2..)]
+        #[arg(add = ArgValueCandidates::new(output_candidates))]
+        outputs: Vec<String>,
+        /// Space to leave between adjacent displays, in logical pixels, such as the width
+        /// of their bezels.
+        ///
+        /// The gap is kept as requested, rather than closed like other gaps in a layout.
+        #[arg(long, default_value_t = 0)]
+        gap: u32,
+        /// Tests the output configuration without applying it.
+        #[arg(long)]
+        test: bool,
+    },
+
+    /// Arrange every enabled display side by side from scratch, ignoring their positions.
+    ///
+    /// The output management protocols have no request for handing placement back to the
+    /// compositor, so the layout is computed here, in connector name order, and applied
+    /// as explicit positions.
+    AutoArrange {
+        /// Tests the output configuration without applying it.
+        #[arg(long)]
+        test: bool,
+    },
+
+    /// Print a completion script for the shell.
+    ///
+    /// Completing output names requires sourcing the script printed by
+    /// `COMPLETE=bash cosmic-randr` instead, which asks `cosmic-randr` for the
+    /// connected outputs.
+    #[command(hide = true)]
+    Completions {
+        /// Shell to print the completion script for.
+        shell: clap_complete::Shell,
+    },
+
+    /// Keep running, applying remembered configurations to displays as they are connected.
+    Daemon {
+        /// Milliseconds that the outputs must stay unchanged before acting on a change,
+        /// so that a burst of hotplugs, as when docking, is handled once.
+        #[arg(long, value_name = "MS", default_value_t = 500)]
+        debounce: u64,
+    },
+
     /// Disable a display
-    Disable { output: String },
+    Disable {
+        /// Name, serial number or model of the output, or `@N` for the Nth output in `list`.
+        #[arg(required_unless_present_any = ["all", "all_but"])]
+        #[arg(add = ArgValueCandidates::new(output_candidates))]
+        output: Option<String>,
+        /// Disable every output. Requires `--force`, as no output will be left enabled.
+        #[arg(long, conflicts_with_all = ["output", "all_but"])]
+        all: bool,
+        /// Disable every output except this one, which is enabled if it isn't already.
+        #[arg(long, value_name = "OUTPUT", conflicts_with = "output")]
+        all_but: Option<String>,
+        /// Allow `--all` to leave no output enabled.
+        #[arg(long, requires = "all")]
+        force: bool,
+        /// Tests the output configuration without applying it.
+        #[arg(long)]
+        test: bool,
+    },
+
+    /// Print a plain text report of the outputs to paste into a bug report or forum post.
+    ///
+    /// The report combines the output of `list` with the protocol versions bound and the
+    /// capabilities of each output, as a single Markdown code block.
+    Share {
+        /// Leave serial numbers out of the report.
+        #[arg(long)]
+        redact_serial: bool,
+    },
+
+    /// Print everything known about the outputs as JSON, for attaching to bug reports.
+    ///
+    /// Unlike `list --json`, this includes protocol details such as bound globals and
+    /// their versions, object IDs, the manager serial and raw protocol values.
+    DumpState,
 
     /// Enable a display
-    Enable { output: String },
+    Enable {
+        /// Name, serial number or model of the output, or `@N` for the Nth output in `list`.
+        #[arg(required_unless_present = "all")]
+        #[arg(add = ArgValueCandidates::new(output_candidates))]
+        output: Option<String>,
+        /// Enable every disabled output at its preferred mode, arranged to the right.
+        #[arg(long, conflicts_with = "output")]
+        all: bool,
+        /// Refresh rate in Hz, such as 144 or 59.94, at the current resolution, or at
+        /// the preferred resolution if the output has no current mode.
+        #[arg(
+            long,
+            visible_alias = "refresh-hz",
+            value_name = "HZ",
+            value_parser = parse_refresh,
+            conflicts_with = "all"
+        )]
+        refresh: Option<f32>,
+        /// Tests the output configuration without applying it.
+        #[arg(long)]
+        test: bool,
+    },
 
     /// Mirror a display
-    Mirror { output: String, from: String },
+    Mirror {
+        /// Name, serial number or model of the output, or `@N` for the Nth output in `list`.
+        #[arg(add = ArgValueCandidates::new(output_candidates))]
+        #[arg(required_unless_present = "all")]
+        output: Option<String>,
+        /// Name of the output to mirror, or `@N` for the Nth output in `list`.
+        #[arg(add = ArgValueCandidates::new(output_candidates))]
+        #[arg(required_unless_present = "all")]
+        from: Option<String>,
+        /// Mirror this output on every other enabled output that isn't already mirroring.
+        #[arg(long, value_name = "FROM", conflicts_with_all = ["output", "from"])]
+        #[arg(add = ArgValueCandidates::new(output_candidates))]
+        all: Option<String>,
+        /// Tests the output configuration without applying it.
+        #[arg(long)]
+        test: bool,
+    },
+
+    /// Enable every connected display and place them side by side from left to right.
+    ///
+    /// Displays are placed in connector name order, after those given with `--order`.
+    /// Mirrored displays are extended, and disabled ones are enabled at their preferred
+    /// mode.
+    Extend {
+        /// Outputs to place first, from left to right, separated by commas, such as
+        /// `eDP-1,DP-1`.
+        #[arg(long, value_delimiter = ',', value_name = "OUTPUTS")]
+        #[arg(add = ArgValueCandidates::new(output_candidates))]
+        order: Vec<String>,
+        /// Tests the output configuration without applying it.
+        #[arg(long)]
+        test: bool,
+    },
+
+    /// Choose modes for displays that share a link, such as daisy-chained DisplayPort
+    /// monitors, so that together they stay within its bandwidth.
+    ///
+    /// Each display starts at its highest resolution and refresh rate, then the display
+    /// using the most bandwidth is stepped down to its next mode until the total fits.
+    Fit {
+        /// Names of the outputs sharing the link.
+        #[arg(long, required = true, num_args = 1..)]
+        #[arg(add = ArgValueCandidates::new(output_candidates))]
+        outputs: Vec<String>,
+        /// Bandwidth available to the displays together, in megabits per second.
+        #[arg(long, value_name = "MBPS")]
+        total_bandwidth: f64,
+        /// Bits per pixel of the signal, such as 24 for 8 bits per color.
+        #[arg(long, default_value_t = 24)]
+        bpp: u32,
+        /// Tests the output configuration without applying it.
+        #[arg(long)]
+        test: bool,
+    },
+
+    /// Print the position and logical size of a display.
+    Geometry {
+        /// Name, serial number or model of the output, or `@N` for the Nth output in `list`.
+        #[arg(required_unless_present = "export_all")]
+        #[arg(add = ArgValueCandidates::new(output_candidates))]
+        output: Option<String>,
+        /// Print shell variables to `eval`, such as `COSMIC_RANDR_DP_1_X`, `_Y`, `_W`
+        /// and `_H`, named after the connector.
+        #[arg(long)]
+        export: bool,
+        /// Print the shell variables of every enabled output.
+        #[arg(long, conflicts_with_all = ["output", "export"])]
+        export_all: bool,
+    },
+
+    /// Apply a layout in the KDL format of `list --kdl`.
+    ///
+    /// The layout is read from the file if one is given, or else from stdin unless it is
+    /// a terminal or empty, or else from the `COSMIC_RANDR_LAYOUT` environment variable.
+    /// Every output of the layout is configured in a single configuration.
+    #[command(visible_alias = "restore")]
+    Kdl {
+        /// File to read the layout from.
+        path: Option<PathBuf>,
+        /// Apply the layout to these outputs only, separated by commas, such as
+        /// `DP-1,DP-2`.
+        ///
+        /// Other outputs are kept as they are, though the protocol requires them to be
+        /// sent again at their current state.
+        #[arg(long, value_delimiter = ',', value_name = "OUTPUTS")]
+        only: Vec<String>,
+        /// Tests the output configuration without applying it.
+        #[arg(long)]
+        test: bool,
+    },
 
     /// List available output heads and modes.
     List {
         /// Display in KDL format.
-        #[arg(long)]
+        #[arg(long, conflicts_with = "json")]
         kdl: bool,
+        /// Indent the KDL across multiple lines for reading. This is the default.
+        #[arg(long, requires = "kdl", conflicts_with = "compact")]
+        pretty: bool,
+        /// Print each output of the KDL on a single line for diffing.
+        #[arg(long, requires = "kdl")]
+        compact: bool,
+        /// Print in another format, with one row per output.
+        #[arg(long, value_enum, conflicts_with = "kdl")]
+        format: Option<ListFormat>,
+        /// Order in which the modes of each output are listed.
+        #[arg(long, value_enum, default_value_t = ModeSort::Res)]
+        sort: ModeSort,
+        /// List modes in ascending rather than descending order.
+        #[arg(long)]
+        asc: bool,
+        /// Order in which the outputs are listed.
+        #[arg(long, value_enum, default_value_t = OutputSort::Name)]
+        sort_outputs: OutputSort,
+        /// Wait up to the `--timeout` until at least this many outputs are connected,
+        /// for scripts that run right after a display is plugged in.
+        #[arg(long, value_name = "OUTPUTS")]
+        wait: Option<usize>,
+        /// Wait until the outputs have not changed for this many milliseconds before
+        /// listing them, for docks and KVMs that identify displays after a delay.
+        #[arg(long, value_name = "MS", default_value_t = 0)]
+        settle: u64,
     },
 
     /// Set a mode for a display.
     Mode(Mode),
 
+    /// Save, apply, or list named mode presets of an output.
+    #[command(subcommand)]
+    ModePreset(ModePresetCommand),
+
+    /// List the modes of a single display.
+    Modes {
+        /// Name, serial number or model of the output, or `@N` for the Nth output in `list`.
+        #[arg(add = ArgValueCandidates::new(output_candidates))]
+        output: String,
+        /// Display in KDL format, as the `modes` node of `list --kdl`.
+        #[arg(long)]
+        kdl: bool,
+        /// Order in which the modes are listed.
+        #[arg(long, value_enum, default_value_t = ModeSort::Res)]
+        sort: ModeSort,
+        /// List modes in ascending rather than descending order.
+        #[arg(long)]
+        asc: bool,
+    },
+
+    /// Switch between the extended layout and mirroring every display, for presenting.
+    #[command(subcommand)]
+    Present(PresentCommand),
+
+    /// Change the refresh rate of a display, keeping its current resolution.
+    Refresh {
+        /// Name, serial number or model of the output, or `@N` for the Nth output in `list`.
+        #[arg(add = ArgValueCandidates::new(output_candidates))]
+        output: String,
+        /// Refresh rate in Hz, such as 144 or 59.94, matched to the nearest mode within
+        /// half a hertz.
+        #[arg(value_parser = parse_refresh)]
+        hz: f32,
+        /// Revert to the previous refresh rate after this long, such as `30s`, `500ms`
+        /// or `2m`, or earlier on Ctrl-C.
+        #[arg(
+            long = "for",
+            value_name = "DURATION",
+            value_parser = parse_duration,
+            conflicts_with = "test"
+        )]
+        duration: Option<Duration>,
+        /// Tests the output configuration without applying it.
+        #[arg(long)]
+        test: bool,
+    },
+
+    /// Remember the current configuration of a display, to be reapplied by `daemon`
+    /// whenever it is connected.
+    ///
+    /// Displays are recognized by their serial number, on any connector.
+    Remember {
+        /// Name, serial number or model of the output, or `@N` for the Nth output in `list`.
+        #[arg(add = ArgValueCandidates::new(output_candidates))]
+        output: String,
+    },
+
+    /// Re-send the current mode of a display to force the compositor to drive it again.
+    ///
+    /// A troubleshooting aid for displays left in a bad state, such as a blank screen
+    /// after a flaky HDMI or DisplayPort link renegotiation.
+    RefreshModeset {
+        /// Name, serial number or model of the output, or `@N` for the Nth output in `list`.
+        #[arg(add = ArgValueCandidates::new(output_candidates))]
+        output: String,
+    },
+
+    /// Save the current layout in the KDL format of `list --kdl`.
+    Snapshot {
+        /// File to write the snapshot to, instead of stdout.
+        path: Option<PathBuf>,
+        /// Skip writing when the file already describes the same layout.
+        ///
+        /// Exits with status 9 when the snapshot was unchanged and not written.
+        #[arg(long, requires = "path")]
+        only_if_changed: bool,
+    },
+
+    /// Check that a KDL layout can be applied to the connected outputs, without applying it.
+    Validate {
+        /// Layout in the KDL format of `list --kdl`.
+        path: PathBuf,
+    },
+
+    /// Recover a broken layout by giving every enabled display a resolution they all
+    /// support, at 100% scale without transforms, arranged from left to right.
+    SafeMode {
+        /// Tests the output configuration without applying it.
+        #[arg(long)]
+        test: bool,
+    },
+
+    /// Choose the scale of a display by the effective DPI it should have.
+    Scale {
+        /// Name, serial number or model of the output, or `@N` for the Nth output in `list`.
+        #[arg(add = ArgValueCandidates::new(output_candidates))]
+        output: String,
+        /// Desired dots per inch after scaling, such as 96 or 110.
+        #[arg(long, value_name = "DPI", required_unless_present = "integer_only")]
+        target_dpi: Option<f64>,
+        /// Reach the logical size of this scale, such as 1.5, with a whole number scale
+        /// and a lower resolution instead of fractional scaling, which blurs X11 clients.
+        #[arg(long, value_name = "FACTOR", conflicts_with = "target_dpi")]
+        integer_only: Option<f64>,
+        /// Tests the output configuration without applying it.
+        #[arg(long)]
+        test: bool,
+    },
+
+    /// Keep running, printing a line for each change to the outputs.
+    ///
+    /// Outputs being connected, disconnected, enabled or disabled are reported, as are
+    /// changes to their mode, position, scale, transform and mirroring.
+    Watch {
+        /// Print the whole layout in the KDL format of `list --kdl` on each change instead.
+        #[arg(long)]
+        kdl: bool,
+        /// Print each output of the KDL on a single line.
+        #[arg(long, requires = "kdl")]
+        compact: bool,
+    },
+
     /// Set position of display.
+    ///
+    /// Positions are in the logical pixels of the global compositor space, in which
+    /// each output is sized by its mode divided by its scale.
     Position {
+        /// Name, serial number or model of the output, or `@N` for the Nth output in `list`.
+        #[arg(add = ArgValueCandidates::new(output_candidates))]
         output: String,
-        x: i32,
-        y: i32,
+        #[arg(
+            allow_hyphen_values(true),
+            required_unless_present_any = ["left_of", "right_of", "above", "below"]
+        )]
+        x: Option<i32>,
+        #[arg(
+            allow_hyphen_values(true),
+            required_unless_present_any = ["left_of", "right_of", "above", "below"]
+        )]
+        y: Option<i32>,
+        /// Unit of the coordinates. Device pixels are divided by the output's scale.
+        #[arg(long, value_enum, default_value_t = Units::Logical)]
+        units: Units,
+        /// Apply the position even if the output would then overlap another.
+        #[arg(long, requires = "x")]
+        allow_overlap: bool,
+        /// Place the output to the left of this one, aligned to its top edge.
+        #[arg(
+            long,
+            value_name = "OUTPUT",
+            conflicts_with_all = ["x", "y", "right_of", "above", "below"]
+        )]
+        left_of: Option<String>,
+        /// Place the output to the right of this one, aligned to its top edge.
+        #[arg(
+            long,
+            value_name = "OUTPUT",
+            conflicts_with_all = ["x", "y", "above", "below"]
+        )]
+        right_of: Option<String>,
+        /// Place the output above this one, aligned to its left edge.
+        #[arg(
+            long,
+            value_name = "OUTPUT",
+            conflicts_with_all = ["x", "y", "below"]
+        )]
+        above: Option<String>,
+        /// Place the output below this one, aligned to its left edge.
+        #[arg(
+            long,
+            value_name = "OUTPUT",
+            conflicts_with_all = ["x", "y"]
+        )]
+        below: Option<String>,
         #[arg(long)]
         test: bool,
     },
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, PartialOrd, Ord, ValueEnum)]
-pub enum Transform {
-    Normal,
-    Rotate90,
-    Rotate180,
-    Rotate270,
-    Flipped,
-    Flipped90,
-    Flipped180,
-    Flipped270,
+/// Side of another output that `position` places an output on.
+#[derive(Clone, Copy, Debug)]
+enum Side {
+    Left,
+    Right,
+    Above,
+    Below,
 }
 
-impl Display for Transform {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(match self {
-            Transform::Normal => "normal",
-            Transform::Rotate90 => "rotate90",
-            Transform::Rotate180 => "rotate180",
-            Transform::Rotate270 => "rotate270",
-            Transform::Flipped => "flipped",
-            Transform::Flipped90 => "flipped90",
-            Transform::Flipped180 => "flipped180",
-            Transform::Flipped270 => "flipped270",
-        })
-    }
+#[derive(clap::Subcommand, Debug)]
+enum ModePresetCommand {
+    /// Save a named mode preset for an output.
+    Save {
+        /// Name of the preset, such as `game` or `work`.
+        name: String,
+        /// Name of the output the preset belongs to.
+        #[arg(add = ArgValueCandidates::new(output_candidates))]
+        output: String,
+        /// Specifies the width of the output picture.
+        width: i32,
+        /// Specifies the height of the output picture.
+        height: i32,
+        /// Refresh rate in Hz, such as 144 or 59.94.
+        #[arg(long, visible_alias = "refresh-hz", value_name = "HZ", value_parser = parse_refresh)]
+        refresh: Option<f32>,
+        /// Specfies the adaptive sync mode to apply to the output.
+        #[arg(long, value_enum)]
+        adaptive_sync: Option<AdaptiveSyncState>,
+        /// Changes the dimensions of the output picture, as a factor such as `1.5` or a
+        /// percentage such as `150%`.
+        #[arg(long, value_parser = parse_scale)]
+        scale: Option<f64>,
+        /// Specifies a transformation matrix to apply to the output.
+        #[arg(long, value_enum)]
+        transform: Option<Transform>,
+    },
+
+    /// Apply a saved mode preset to an output.
+    Apply {
+        /// Name of the preset.
+        name: String,
+        /// Name, serial number or model of the output, or `@N` for the Nth output in `list`.
+        #[arg(add = ArgValueCandidates::new(output_candidates))]
+        output: String,
+        /// Tests the output configuration without applying it.
+        #[arg(long)]
+        test: bool,
+    },
+
+    /// List the mode presets saved for an output.
+    List {
+        /// Name of the output.
+        #[arg(add = ArgValueCandidates::new(output_candidates))]
+        output: String,
+    },
 }
 
-impl TryFrom<WlTransform> for Transform {
-    type Error = &'static str;
-
-    fn try_from(transform: WlTransform) -> Result<Self, Self::Error> {
-        Ok(match transform {
-            WlTransform::Normal => Transform::Normal,
-            WlTransform::_90 => Transform::Rotate90,
-            WlTransform::_180 => Transform::Rotate180,
-            WlTransform::_270 => Transform::Rotate270,
-            WlTransform::Flipped => Transform::Flipped,
-            WlTransform::Flipped90 => Transform::Flipped90,
-            WlTransform::Flipped180 => Transform::Flipped180,
-            WlTransform::Flipped270 => Transform::Flipped270,
-            _ => return Err("unknown wl_transform variant"),
-        })
-    }
+#[derive(clap::Subcommand, Debug)]
+enum PresentCommand {
+    /// Mirror every display onto one, saving the extended layout, or restore the saved
+    /// layout if the displays are already mirrored this way.
+    ///
+    /// When the displays were connected or disconnected in the meantime, the saved
+    /// layout no longer fits, and the displays are arranged from left to right instead.
+    Toggle {
+        /// Display that the others mirror. Defaults to the leftmost display.
+        #[arg(long)]
+        primary: Option<String>,
+    },
 }
 
-impl Transform {
-    #[must_use]
-    pub fn wl_transform(self) -> WlTransform {
-        match self {
-            Transform::Normal => WlTransform::Normal,
-            Transform::Rotate90 => WlTransform::_90,
-            Transform::Rotate180 => WlTransform::_180,
-            Transform::Rotate270 => WlTransform::_270,
-            Transform::Flipped => WlTransform::Flipped,
-            Transform::Flipped90 => WlTransform::Flipped90,
-            Transform::Flipped180 => WlTransform::Flipped180,
-            Transform::Flipped270 => WlTransform::Flipped270,
-        }
-    }
+/// Unit of coordinates given on the command line.
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum Units {
+    /// Pixels of the global compositor space, which the protocol uses.
+    Logical,
+    /// Physical pixels of the output, before its scale is applied.
+    Device,
+}
+
+/// Tabular formats of `list`.
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum ListFormat {
+    /// Comma-separated values, with a header row, for spreadsheets and logs.
+    Csv,
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
-pub enum AdaptiveSync {
-    #[value(name = "true")]
+/// When to color the human-readable output of `list` and `modes`.
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum ColorChoice {
+    /// Color when stdout is a terminal and `NO_COLOR` is unset or empty.
+    Auto,
+    /// Always color, such as for a pager that renders colors.
     Always,
-    #[value(name = "automatic")]
-    Automatic,
-    #[value(name = "false")]
-    Disabled,
+    /// Never color.
+    Never,
 }
 
-impl Display for AdaptiveSync {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(match self {
-            AdaptiveSync::Always => "true",
-            AdaptiveSync::Automatic => "automatic",
-            AdaptiveSync::Disabled => "false",
-        })
+impl ColorChoice {
+    fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Auto => {
+                !matches!(std::env::var_os("NO_COLOR"), Some(value) if !value.is_empty())
+                    && std::io::stdout().is_terminal()
+            }
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
     }
 }
 
-impl TryFrom<AdaptiveSyncStateExt> for AdaptiveSync {
-    type Error = &'static str;
+/// Order of the outputs printed by `list`.
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum OutputSort {
+    /// By connector name, so that repeated listings are identical.
+    Name,
+    /// From left to right, then from top to bottom.
+    Position,
+    /// Enabled outputs first, then by connector name.
+    Enabled,
+}
 
-    fn try_from(value: AdaptiveSyncStateExt) -> Result<Self, Self::Error> {
-        Ok(match value {
-            AdaptiveSyncStateExt::Always => AdaptiveSync::Always,
-            AdaptiveSyncStateExt::Automatic => AdaptiveSync::Automatic,
-            AdaptiveSyncStateExt::Disabled => AdaptiveSync::Disabled,
-            _ => return Err("unknown adaptive_sync_state_ext variant"),
-        })
+impl OutputSort {
+    /// Sorts heads that are already in connector name order.
+    fn sort(self, heads: &mut [&OutputHead]) {
+        match self {
+            OutputSort::Name => (),
+            OutputSort::Position => heads.sort_by_key(|head| (head.position_x, head.position_y)),
+            OutputSort::Enabled => heads.sort_by_key(|head| !head.enabled),
+        }
     }
 }
 
-impl AdaptiveSync {
-    #[must_use]
-    pub fn adaptive_sync_state_ext(self) -> AdaptiveSyncStateExt {
+/// Order of the modes printed by `list`, from highest to lowest.
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum ModeSort {
+    /// By width, then height, then refresh rate.
+    Res,
+    /// By refresh rate, then resolution.
+    Refresh,
+    /// By pixel count, then refresh rate.
+    Pixels,
+}
+
+impl ModeSort {
+    fn cmp(self, a: &OutputMode, b: &OutputMode) -> std::cmp::Ordering {
+        // The `Ord` of `OutputMode` already sorts by descending resolution and refresh.
         match self {
-            AdaptiveSync::Always => AdaptiveSyncStateExt::Always,
-            AdaptiveSync::Automatic => AdaptiveSyncStateExt::Automatic,
-            AdaptiveSync::Disabled => AdaptiveSyncStateExt::Disabled,
+            ModeSort::Res => a.cmp(b),
+            ModeSort::Refresh => b.refresh.cmp(&a.refresh).then_with(|| a.cmp(b)),
+            ModeSort::Pixels => (b.width * b.height)
+                .cmp(&(a.width * a.height))
+                .then_with(|| b.refresh.cmp(&a.refresh)),
         }
     }
 }
 
+/// Refresh rates above this many Hz were most likely given in mHz by mistake.
+const MAX_REFRESH_HZ: f32 = 1000.0;
+
+/// Exit status of `snapshot --only-if-changed` when the file was left untouched.
+const EXIT_UNCHANGED: i32 = 9;
+
+/// Exit status for invalid arguments, as clap's own status 2 means a cancelled
+/// configuration.
+const EXIT_USAGE: i32 = 64;
+
+/// How a command that succeeded ended, which scripts tell apart by the exit status.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Outcome {
+    /// The command did what was asked.
+    Done,
+    /// `snapshot --only-if-changed` left the file untouched.
+    Unchanged,
+}
+
 #[tokio::main(flavor = "current_thread")]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let cli = Cli::parse();
+async fn main() {
+    CompleteEnv::with_factory(Cli::command).complete();
+    let cli = Cli::try_parse().unwrap_or_else(|why| {
+        let _res = why.print();
+        std::process::exit(if why.use_stderr() { EXIT_USAGE } else { 0 });
+    });
+    let json = cli.json;
+
+    match run(cli).await {
+        Ok(Outcome::Done) => (),
+        Ok(Outcome::Unchanged) => std::process::exit(EXIT_UNCHANGED),
+        Err(why) => {
+            error::report(&*why, json);
+            std::process::exit(error::Kind::of(&*why).exit_code());
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<Outcome, Box<dyn std::error::Error>> {
+    if cli.no_cosmic {
+        std::env::set_var(cosmic_randr::NO_COSMIC_ENV, "1");
+    }
+
+    // Packaging generates completions where no compositor may be running.
+    if let Commands::Completions { shell } = cli.command {
+        let mut command = Cli::command();
+        let name = command.get_name().to_string();
+        clap_complete::generate(shell, &mut command, name, &mut std::io::stdout());
+        return Ok(Outcome::Done);
+    }
 
     let (message_tx, message_rx) = tachyonix::channel(5);
 
-    let (context, event_queue) = cosmic_randr::connect(message_tx)?;
+    let (mut context, event_queue) = cosmic_randr::connect(message_tx)?;
+    context.dry_run = cli.dry_run;
 
     let mut app = App {
         context,
         event_queue,
         message_rx,
+        apply_timeout: Duration::from_millis(cli.apply_timeout),
+        timeout: Duration::from_millis(cli.timeout),
+        verbose: cli.verbose,
+        color: cli.color.enabled(),
     };
 
-    match cli.command {
-        Commands::Enable { output } => app.enable(&output).await,
+    let mut outcome = Outcome::Done;
+
+    let result = match cli.command {
+        Commands::AddMode {
+            output,
+            width,
+            height,
+            refresh,
+            test,
+        } => app.add_mode(&output, width, height, refresh, test).await,
+
+        Commands::Arrange { outputs, gap, test } => app.arrange(&outputs, gap, test).await,
+
+        Commands::AutoArrange { test } => app.auto_arrange(test).await,
+
+        // Generated before connecting to the compositor.
+        Commands::Completions { .. } => Ok(()),
+
+        Commands::Daemon { debounce } => app.daemon(Duration::from_millis(debounce)).await,
+
+        Commands::DumpState => app.dump_state().await,
+
+        Commands::Share { redact_serial } => app.share(redact_serial).await,
+
+        Commands::Enable {
+            output,
+            all,
+            refresh,
+            test,
+        } => match output {
+            Some(output) if !all => app.enable(&output, refresh, test).await,
+            _ => app.enable_all(test).await,
+        },
+
+        Commands::Mirror {
+            output,
+            from,
+            all,
+            test,
+        } => match (output, from, all) {
+            (_, _, Some(from)) => app.mirror_all(&from, test).await,
+            (Some(output), Some(from), None) => app.mirror(&output, &from, test).await,
+            _ => Err("an output and the output to mirror, or --all, are required".into()),
+        },
+
+        Commands::Disable {
+            output,
+            all,
+            all_but,
+            force,
+            test,
+        } => match output {
+            Some(output) if !all => app.disable(&output, test).await,
+            _ => app.disable_all(all_but.as_deref(), force, test).await,
+        },
+
+        Commands::Extend { order, test } => app.extend(&order, test).await,
+
+        Commands::Fit {
+            outputs,
+            total_bandwidth,
+            bpp,
+            test,
+        } => app.fit(&outputs, total_bandwidth, bpp, test).await,
+
+        Commands::Geometry {
+            output,
+            export,
+            export_all,
+        } => {
+            app.geometry(output.as_deref(), export || export_all, cli.json)
+                .await
+        }
 
-        Commands::Mirror { output, from } => app.mirror(&output, &from).await,
+        Commands::Kdl { path, only, test } => {
+            let list = parse_kdl_list(&read_layout(path.as_deref())?)?;
+            app.apply_list(&list, &only, test).await
+        }
 
-        Commands::Disable { output } => app.disable(&output).await,
+        Commands::List {
+            kdl,
+            pretty: _,
+            compact,
+            format,
+            sort,
+            asc,
+            sort_outputs,
+            wait,
+            settle,
+        } => {
+            if cli.json && format.is_some() {
+                return Err("--format cannot be used with --json".into());
+            }
 
-        Commands::List { kdl } => app.list(kdl).await,
+            app.list(
+                kdl,
+                compact,
+                format,
+                cli.json,
+                sort,
+                asc,
+                sort_outputs,
+                wait,
+                Duration::from_millis(settle),
+            )
+            .await
+        }
 
         Commands::Mode(mode) => app.mode(mode).await,
 
-        Commands::Position { output, x, y, test } => app.set_position(&output, x, y, test).await,
+        Commands::ModePreset(command) => app.mode_preset(command).await,
+
+        Commands::Modes {
+            output,
+            kdl,
+            sort,
+            asc,
+        } => app.modes(&output, kdl, cli.json, sort, asc).await,
+
+        Commands::Present(PresentCommand::Toggle { primary }) => {
+            app.present_toggle(primary.as_deref()).await
+        }
+
+        Commands::Refresh {
+            output,
+            hz,
+            duration,
+            test,
+        } => app.refresh(&output, hz, duration, test).await,
+
+        Commands::Remember { output } => app.remember(&output).await,
+
+        Commands::RefreshModeset { output } => app.refresh_modeset(&output).await,
+
+        Commands::SafeMode { test } => app.safe_mode(test).await,
+
+        Commands::Scale {
+            output,
+            target_dpi,
+            integer_only,
+            test,
+        } => match (integer_only, target_dpi) {
+            (Some(factor), _) => app.integer_scale(&output, factor, test).await,
+            (None, Some(target_dpi)) => app.scale_to_dpi(&output, target_dpi, test).await,
+            (None, None) => Err("a target DPI or an integer-only factor is required".into()),
+        },
+
+        Commands::Snapshot {
+            path,
+            only_if_changed,
+        } => app
+            .snapshot(path.as_deref(), only_if_changed)
+            .await
+            .map(|snapshot| outcome = snapshot),
+
+        Commands::Validate { path } => app.validate(&path, cli.json).await,
+
+        Commands::Position {
+            output,
+            x,
+            y,
+            units,
+            allow_overlap,
+            left_of,
+            right_of,
+            above,
+            below,
+            test,
+        } => {
+            let beside = [
+                (Side::Left, left_of),
+                (Side::Right, right_of),
+                (Side::Above, above),
+                (Side::Below, below),
+            ]
+            .into_iter()
+            .find_map(|(side, anchor)| Some((side, anchor?)));
+
+            match (beside, x.zip(y)) {
+                (Some((side, anchor)), _) => app.place_beside(&output, side, &anchor, test).await,
+                (None, Some((x, y))) => {
+                    app.set_position(&output, x, y, units, allow_overlap, test)
+                        .await
+                }
+                (None, None) => Err("a position or a relative placement is required".into()),
+            }
+        }
+
+        Commands::Watch { kdl, compact } => app.watch(kdl, compact).await,
+    };
+
+    // Name the missing features when the COSMIC extension is too old for a request,
+    // and the connected outputs when a request names another.
+    if let Err(why) = &result {
+        if !cli.json && error::Kind::of(&**why) == error::Kind::Unsupported {
+            if let Some(note) = app.context.cosmic_capabilities.partial_support() {
+                eprintln!("note: {note}");
+            }
+        }
+
+        if !cli.json && error::Kind::of(&**why) == error::Kind::UnknownOutput {
+            let names = sorted_heads(&app.context)
+                .into_iter()
+                .map(|head| head.name.as_str())
+                .collect::<Vec<_>>();
+
+            if !names.is_empty() {
+                eprintln!("note: connected outputs: {}", names.join(", "));
+            }
+        }
     }
+
+    result.map(|()| outcome)
 }
 
 struct App {
     context: Context,
     event_queue: EventQueue<Context>,
     message_rx: Receiver<Message>,
+    /// Time allowed for a configuration to succeed or fail, which may involve a modeset.
+    apply_timeout: Duration,
+    /// Time to wait for the compositor to report the outputs.
+    timeout: Duration,
+    /// Print notes that are informative but not required to act upon.
+    verbose: bool,
+    /// Color the human-readable output.
+    color: bool,
 }
 
 impl App {
-    // Ignores any messages other than `ManagerDone`
-    async fn dispatch_until_manager_done(&mut self) -> Result<(), cosmic_randr::Error> {
+    /// Waits for the compositor to finish reporting the outputs, within the timeout.
+    async fn dispatch_until_manager_done(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let timeout = self.timeout;
+        tokio::time::timeout(timeout, self.wait_for_manager_done())
+            .await
+            .map_err(|_| CliError::NoResponse(timeout))?
+    }
+
+    /// Waits however long it takes for the next `ManagerDone`, as when watching for
+    /// changes, ignoring any messages other than `ManagerDone` and `ManagerFinished`.
+    async fn wait_for_manager_done(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         'outer: loop {
             while let Ok(msg) = self.message_rx.try_recv() {
-                if matches!(msg, Message::ManagerDone) {
-                    break 'outer;
+                match msg {
+                    Message::ManagerDone => break 'outer,
+                    Message::ManagerFinished => return Err(CliError::ManagerFinished.into()),
+                    Message::Unsupported => return Err(CliError::Unsupported.into()),
+                    _ => (),
                 }
             }
             self.context.dispatch(&mut self.event_queue).await?;
@@ -260,8 +1160,22 @@ impl App {
 
     /// # Errors
     ///
-    /// Returns error if the message receiver fails, dispach fails, or a configuration failed.
+    /// Returns error if the message receiver fails, dispach fails, a configuration failed,
+    /// or the compositor did not respond within the apply timeout.
     async fn receive_config_messages(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let timeout = self.apply_timeout;
+        tokio::time::timeout(timeout, self.wait_for_config_messages())
+            .await
+            .map_err(|_| CliError::Timeout(timeout))??;
+
+        if self.context.dry_run {
+            eprintln!("dry run: the compositor accepts this configuration; nothing was applied");
+        }
+
+        Ok(())
+    }
+
+    async fn wait_for_config_messages(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         loop {
             while let Ok(message) = self.message_rx.try_recv() {
                 if config_message(Ok(message))? {
@@ -273,188 +1187,42 @@ impl App {
         }
     }
 
-    async fn enable(&mut self, output: &str) -> Result<(), Box<dyn std::error::Error>> {
-        self.dispatch_until_manager_done().await?;
-        enable(&mut self.context, output)?;
-        self.receive_config_messages().await?;
-
-        Ok(())
-    }
-
-    async fn mirror(&mut self, output: &str, from: &str) -> Result<(), Box<dyn std::error::Error>> {
-        self.dispatch_until_manager_done().await?;
-        mirror(&mut self.context, output, from)?;
-        self.receive_config_messages().await
-    }
-
-    async fn disable(&mut self, output: &str) -> Result<(), Box<dyn std::error::Error>> {
-        self.dispatch_until_manager_done().await?;
-        disable(&mut self.context, output)?;
-        self.receive_config_messages().await
-    }
-
-    async fn list(&mut self, kdl: bool) -> Result<(), Box<dyn std::error::Error>> {
-        self.dispatch_until_manager_done().await?;
-        for head in self.context.output_heads.values_mut() {
-            head.modes
-                .sort_unstable_by(|_, either, _, or| either.cmp(or));
-        }
-
-        if kdl {
-            list_kdl(&self.context);
-        } else {
-            list(&self.context);
+    /// Keeps receiving changes to the outputs until none arrives for the `window`.
+    ///
+    /// Restarts the wait on every change, so that a burst of changes, such as hotplugs
+    /// while docking or a KVM reporting the real make and model of a display after a
+    /// generic one, is seen in its final state.
+    async fn settle(&mut self, window: Duration) -> Result<(), Box<dyn std::error::Error>> {
+        while let Ok(result) = tokio::time::timeout(window, self.wait_for_manager_done()).await {
+            result?;
         }
 
         Ok(())
     }
 
-    async fn mode(&mut self, mode: Mode) -> Result<(), Box<dyn std::error::Error>> {
-        self.dispatch_until_manager_done().await?;
-        set_mode(&mut self.context, &mode)?;
-        self.receive_config_messages().await?;
-        self.auto_correct_offsets(&mode.output, mode.test).await
-    }
-
-    async fn set_position(
+    async fn apply_head_config(
         &mut self,
         output: &str,
-        x: i32,
-        y: i32,
-        test: bool,
+        head_config: HeadConfiguration,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        self.dispatch_until_manager_done().await?;
-        set_position(&mut self.context, output, x, y, test)?;
-        self.receive_config_messages().await?;
-        self.auto_correct_offsets(output, test).await
+        let mut config = self.context.create_output_config()?;
+        config.enable_head(output, Some(head_config))?;
+        submit(config, false)?;
+        self.receive_config_messages().await
     }
 
-    // Offset outputs in case of negative positioning.
-    async fn auto_correct_offsets(
-        &mut self,
-        output: &str,
-        test: bool,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        // Get the position and dimensions of the moved display.
-        let Some(ref mut active_output) = self
-            .context
-            .output_heads
-            .values()
-            .find(|head| head.name == output)
-            .and_then(|head| {
-                let Some(ref mode) = head.current_mode else {
-                    return None;
-                };
-
-                let Some(mode) = head.modes.get(mode) else {
-                    return None;
-                };
-
-                let (width, height) = if head.transform.map_or(true, |wl_transform| {
-                    Transform::try_from(wl_transform).map_or(true, is_landscape)
-                }) {
-                    (mode.width, mode.height)
+    /// Sorts the modes of every output for display.
+    fn sort_modes(&mut self, sort: ModeSort, ascending: bool) {
+        for head in self.context.output_heads.values_mut() {
+            head.modes.sort_unstable_by(|_, either, _, or| {
+                let ordering = sort.cmp(either, or);
+                if ascending {
+                    ordering.reverse()
                 } else {
-                    (mode.height, mode.width)
-                };
-
-                Some(align::Rectangle {
-                    x: head.position_x as f32,
-                    y: head.position_y as f32,
-                    width: width as f32 / head.scale as f32,
-                    height: height as f32 / head.scale as f32,
-                })
-            })
-        else {
-            return Ok(());
-        };
-
-        // Create an iterator of other outputs and their positions and dimensions.
-        let other_outputs = self.context.output_heads.values().filter_map(|head| {
-            if head.name == output {
-                None
-            } else {
-                let Some(ref mode) = head.current_mode else {
-                    return None;
-                };
-
-                let Some(mode) = head.modes.get(mode) else {
-                    return None;
-                };
-
-                if !head.enabled || head.mirroring.is_some() {
-                    return None;
+                    ordering
                 }
-
-                let (width, height) = if head.transform.map_or(true, |wl_transform| {
-                    Transform::try_from(wl_transform).map_or(true, is_landscape)
-                }) {
-                    (mode.width, mode.height)
-                } else {
-                    (mode.height, mode.width)
-                };
-
-                Some(align::Rectangle {
-                    x: head.position_x as f32,
-                    y: head.position_y as f32,
-                    width: width as f32 / head.scale as f32,
-                    height: height as f32 / head.scale as f32,
-                })
-            }
-        });
-
-        // Align outputs such that there are no gaps.
-        align::display(active_output, other_outputs);
-
-        // Calculate how much to offset the position of each display to be aligned against (0,0)
-        let mut offset = self
-            .context
-            .output_heads
-            .values()
-            .filter(|head| head.enabled && head.mirroring.is_none())
-            .fold((i32::MAX, i32::MAX), |offset, head| {
-                let (x, y) = if output == head.name {
-                    (active_output.x as i32, active_output.y as i32)
-                } else {
-                    (head.position_x, head.position_y)
-                };
-
-                (offset.0.min(x), offset.1.min(y))
             });
-
-        // Reposition each display with that offset
-        let updates = self
-            .context
-            .output_heads
-            .values()
-            .filter(|head| head.enabled && head.mirroring.is_none())
-            .map(|head| {
-                let (x, y) = if output == head.name {
-                    (active_output.x as i32, active_output.y as i32)
-                } else {
-                    (head.position_x, head.position_y)
-                };
-
-                (head.name.clone(), x - offset.0, y - offset.1)
-            })
-            .collect::<Vec<_>>();
-
-        // Adjust again to (0,0) baseline
-        offset = updates
-            .iter()
-            .fold((i32::MAX, i32::MAX), |offset, (_, x, y)| {
-                (offset.0.min(*x), offset.1.min(*y))
-            });
-
-        // Apply new positions
-        for (name, mut x, mut y) in updates {
-            x -= offset.0;
-            y -= offset.1;
-            set_position(&mut self.context, &name, x, y, test)?;
-            self.receive_config_messages().await?;
         }
-
-        Ok(())
     }
 }
 
@@ -468,262 +1236,43 @@ pub fn config_message(
     message: Result<cosmic_randr::Message, tachyonix::RecvError>,
 ) -> Result<bool, Box<dyn std::error::Error>> {
     match message {
-        Ok(cosmic_randr::Message::ConfigurationCancelled) => Err("configuration cancelled".into()),
+        Ok(cosmic_randr::Message::ConfigurationCancelled) => {
+            Err(CliError::ConfigurationCancelled.into())
+        }
 
-        Ok(cosmic_randr::Message::ConfigurationFailed) => Err("configuration failed".into()),
+        Ok(cosmic_randr::Message::ConfigurationFailed) => Err(CliError::ConfigurationFailed.into()),
 
         Ok(cosmic_randr::Message::ConfigurationSucceeded) => Ok(true),
 
+        Ok(cosmic_randr::Message::ManagerFinished) => Err(CliError::ManagerFinished.into()),
+
         Err(why) => Err(format!("channel error: {why:?}").into()),
 
         _ => Ok(false),
     }
 }
 
-fn disable(context: &mut Context, output: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let mut config = context.create_output_config();
-    config.disable_head(output)?;
-    config.apply();
-
-    Ok(())
-}
-
-fn enable(context: &mut Context, output: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let mut config = context.create_output_config();
-    config.enable_head(output, None)?;
-    config.apply();
-
-    Ok(())
-}
-
-fn mirror(
-    context: &mut Context,
-    output: &str,
-    from: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let mut config = context.create_output_config();
-    config.mirror_head(output, from, None)?;
-    config.apply();
-
-    Ok(())
-}
-
-fn list(context: &Context) {
-    let mut output = String::new();
-    let mut resolution = String::new();
-
-    for head in context.output_heads.values() {
-        #[allow(clippy::ignored_unit_patterns)]
-        let _res = fomat_macros::witeln!(
-            &mut output,
-            (Style::new().bold().paint(&head.name)) " "
-            if head.enabled {
-                if let Some(from) = head.mirroring.as_ref() {
-                    (Color::Blue.bold().paint(format!("(mirroring \"{}\")", from)))
-                } else {
-                    (Color::Green.bold().paint("(enabled)"))
-                }
-            } else {
-                (Color::Red.bold().paint("(disabled)"))
-            }
-            if !head.make.is_empty() {
-                (Color::Yellow.bold().paint("\n  Make: ")) (head.make)
-            }
-            (Color::Yellow.bold().paint("\n  Model: "))
-            (head.model)
-            (Color::Yellow.bold().paint("\n  Physical Size: "))
-            (head.physical_width) " x " (head.physical_height) " mm"
-            (Color::Yellow.bold().paint("\n  Position: "))
-            (head.position_x) "," (head.position_y)
-            (Color::Yellow.bold().paint("\n  Scale: ")) ((head.scale * 100.0) as i32) "%"
-            if let Some(wl_transform) = head.transform {
-                if let Ok(transform) = Transform::try_from(wl_transform) {
-                    (Color::Yellow.bold().paint("\n  Transform: ")) (transform)
-                }
-            }
-            if let Some(available) = head.adaptive_sync_support {
-                (Color::Yellow.bold().paint("\n  Adaptive Sync Support: "))
-                (match available {
-                    AdaptiveSyncAvailability::Supported | AdaptiveSyncAvailability::RequiresModeset => Color::Green.paint("true"),
-                    _ => Color::Red.paint("false"),
-                })
-            }
-            if let Some(sync) = head.adaptive_sync {
-                (Color::Yellow.bold().paint("\n  Adaptive Sync: "))
-                (match sync {
-                    AdaptiveSyncStateExt::Always => {
-                        Color::Green.paint("true\n")
-                    },
-                    AdaptiveSyncStateExt::Automatic => {
-                        Color::Green.paint("automatic\n")
-                    },
-                    _ => {
-                        Color::Red.paint("false\n")
-                    }
-                })
-            }
-            (Color::Yellow.bold().paint("\n  Modes:"))
-        );
-
-        for mode in head.modes.values() {
-            resolution.clear();
-            let _res = write!(&mut resolution, "{}x{}", mode.width, mode.height);
-
-            let _res = writeln!(
-                &mut output,
-                "    {:>9} @ {}{}{}",
-                Color::Magenta.paint(format!("{resolution:>9}")),
-                Color::Cyan.paint(format!(
-                    "{:>3}.{:03} Hz",
-                    mode.refresh / 1000,
-                    mode.refresh % 1000
-                )),
-                if head.current_mode.as_ref() == Some(&mode.wlr_mode.id()) {
-                    Color::Purple.bold().paint(" (current)")
-                } else {
-                    Color::default().paint("")
-                },
-                if mode.preferred {
-                    Color::Green.bold().paint(" (preferred)")
-                } else {
-                    Color::default().paint("")
-                }
-            );
-        }
-    }
-
-    let mut stdout = std::io::stdout().lock();
-    let _res = stdout.write_all(output.as_bytes());
-    let _res = stdout.flush();
-}
-
-fn list_kdl(context: &Context) {
-    let mut output = String::new();
-
-    for head in context.output_heads.values() {
-        #[allow(clippy::ignored_unit_patterns)]
-        let _res = fomat_macros::witeln!(
-            &mut output,
-            "output \"" (head.name) "\" enabled=" (head.enabled) " {\n"
-            "  description"
-            if !head.make.is_empty() { " make=\"" (head.make) "\"" }
-            " model=\"" (head.model) "\"\n"
-            "  physical " (head.physical_width) " " (head.physical_height) "\n"
-            "  position " (head.position_x) " " (head.position_y) "\n"
-            "  scale " (format!("{:.2}", head.scale)) "\n"
-            if let Some(mirroring) = head.mirroring.as_ref() {
-                "  mirroring \"" (mirroring) "\"\n"
-            }
-            if let Some(wl_transform) = head.transform {
-                if let Ok(transform) = Transform::try_from(wl_transform) {
-                    "  transform \"" (transform) "\"\n"
-                }
-            }
-            if let Some(available) = head.adaptive_sync_support {
-                "  adaptive_sync_support \""
-                (match available {
-                    AdaptiveSyncAvailability::Supported => "true",
-                    AdaptiveSyncAvailability::RequiresModeset => "requires_modeset",
-                    _ => "false",
-                })
-                "\"\n"
-            }
-            if let Some(sync) = head.adaptive_sync {
-                "  adaptive_sync \""
-                (match sync {
-                    AdaptiveSyncStateExt::Always => "true",
-                    AdaptiveSyncStateExt::Automatic => "automatic",
-                    _ => "false",
-                })
-                "\"\n"
-            }
-            if !head.serial_number.is_empty() {
-                "  serial_number=\"" (head.serial_number) "\"\n"
-            }
-            "  modes {"
-        );
-
-        for mode in head.modes.values() {
-            let _res = writeln!(
-                &mut output,
-                "    mode {} {} {}{}{}",
-                mode.width,
-                mode.height,
-                mode.refresh,
-                if head.current_mode.as_ref() == Some(&mode.wlr_mode.id()) {
-                    " current=true"
-                } else {
-                    ""
-                },
-                if mode.preferred {
-                    " preferred=true"
-                } else {
-                    ""
-                },
-            );
+/// Applies the configuration, or only tests it if `test` is set.
+///
+/// Under `--dry-run`, every configuration is only tested, and the changes that it
+/// makes are printed first.
+fn submit(config: Configuration, test: bool) -> Result<(), UnconfiguredHeads> {
+    if config.is_dry_run() {
+        for change in config.changes() {
+            eprintln!("dry run: {change}");
         }
-
-        let _res = writeln!(&mut output, "  }}\n}}");
-    }
-
-    let mut stdout = std::io::stdout().lock();
-    let _res = stdout.write_all(output.as_bytes());
-    let _res = stdout.flush();
-}
-
-fn set_mode(context: &mut Context, args: &Mode) -> Result<(), Box<dyn std::error::Error>> {
-    let mirroring = context
-        .output_heads
-        .values()
-        .find(|output| output.name == args.output)
-        .and_then(|head| head.mirroring.clone());
-
-    let mut config = context.create_output_config();
-    let head_config = args.to_head_config();
-
-    if let Some(mirroring_from) = mirroring.filter(|_| head_config.pos.is_none()) {
-        config.mirror_head(&args.output, &mirroring_from, Some(head_config))?;
-    } else {
-        config.enable_head(&args.output, Some(head_config))?;
     }
 
-    if args.test {
-        config.test();
+    if test {
+        config.test()
     } else {
-        config.apply();
+        config.apply()
     }
-
-    Ok(())
 }
 
-fn set_position(
-    context: &mut Context,
-    name: &str,
-    x: i32,
-    y: i32,
-    test: bool,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let mut config = context.create_output_config();
-    config.enable_head(
-        name,
-        Some(HeadConfiguration {
-            pos: Some((x, y)),
-            ..Default::default()
-        }),
-    )?;
-
+/// Tells that the compositor accepted a configuration that was only tested.
+fn test_passed(test: bool) {
     if test {
-        config.test();
-    } else {
-        config.apply();
+        println!("test passed: the compositor accepts this configuration");
     }
-
-    Ok(())
-}
-
-fn is_landscape(transform: Transform) -> bool {
-    matches!(
-        transform,
-        Transform::Normal | Transform::Rotate180 | Transform::Flipped | Transform::Flipped180
-    )
 }
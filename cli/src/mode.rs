@@ -0,0 +1,918 @@
+// Copyright 2023 System76 <info@system76.com>
+// SPDX-License-Identifier: MPL-2.0
+
+//! Modes, refresh rates and scales of single outputs, with `mode`, `refresh` and the
+//! commands that pick a mode for the user.
+
+use crate::error::CliError;
+use crate::geometry::{
+    centered_position, check_overlap, fractional_scale_note, head_transform, layout,
+    requested_logical_size, transform_note, xwayland_scale_note,
+};
+use crate::list::{print_text, write_modes};
+use crate::outputs::{context_has_output, resolve_output, sorted_heads};
+use crate::{json, submit, test_passed, validate, App, Mode, ModeSort, Refresh};
+use cosmic_randr::context::{ConfigurationError, HeadConfiguration};
+use cosmic_randr::output_head::OutputHead;
+use cosmic_randr::output_mode::{select_mode, ModeTiming};
+use cosmic_randr::{Context, OutputMode};
+use cosmic_randr_shell::AdaptiveSyncState;
+use kdl::{KdlDocument, KdlNode};
+use std::collections::BTreeSet;
+use std::io::Write;
+use std::time::Duration;
+use wayland_client::protocol::wl_output::Transform as WlTransform;
+use wayland_client::Proxy;
+
+/// Delay before the first retry of `mode --retry-vrr`, doubled on each further retry.
+const VRR_RETRY_DELAY: Duration = Duration::from_millis(250);
+
+impl App {
+    /// Enables the output at a custom mode, which it does not need to advertise.
+    pub async fn add_mode(
+        &mut self,
+        output: &str,
+        width: i32,
+        height: i32,
+        refresh: Option<f32>,
+        test: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.dispatch_until_manager_done().await?;
+        let output = resolve_output(&self.context, output)?;
+
+        if !context_has_output(&self.context, &output) {
+            return Err(CliError::UnknownOutput(output).into());
+        }
+
+        if width <= 0 || height <= 0 {
+            return Err(format!("{width}x{height} is not a valid mode size").into());
+        }
+
+        // Rounded, as truncating would turn 59.94 into 59939 mHz.
+        let refresh = refresh.map_or(0, |hz| (hz * 1000.0).round() as i32);
+
+        let mut config = self.context.create_output_config()?;
+        config.enable_head_custom_mode(&output, width, height, refresh)?;
+        submit(config, test)?;
+        self.receive_config_messages().await?;
+
+        let prefix = if test { "would be " } else { "" };
+        println!("{output}: {prefix}set to custom mode {width}x{height}");
+        test_passed(test);
+        Ok(())
+    }
+
+    /// Prints the sorted modes of one output, marking the current and preferred modes.
+    pub async fn modes(
+        &mut self,
+        output: &str,
+        kdl: bool,
+        json: bool,
+        sort: ModeSort,
+        ascending: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.dispatch_until_manager_done().await?;
+        let output = resolve_output(&self.context, output)?;
+        self.sort_modes(sort, ascending);
+
+        let head = self
+            .context
+            .output_heads
+            .values()
+            .find(|head| head.name == output)
+            .ok_or(CliError::UnknownOutput(output))?;
+
+        if json {
+            json::print(json::Modes {
+                name: head.name.clone(),
+                modes: json::Mode::of_head(head),
+            })?;
+        } else if kdl {
+            let mut modes = KdlNode::new("modes");
+            for mode in head.modes.values() {
+                let current = head.current_mode.as_ref() == Some(&mode.wlr_mode.id());
+                modes
+                    .ensure_children()
+                    .nodes_mut()
+                    .push(cosmic_randr_shell::Mode::from(mode).to_kdl(current));
+            }
+
+            let mut document = KdlDocument::new();
+            document.nodes_mut().push(modes);
+            document.fmt();
+
+            let mut stdout = std::io::stdout().lock();
+            let _res = write!(stdout, "{document}");
+            let _res = stdout.flush();
+        } else {
+            let mut text = String::new();
+            write_modes(&mut text, head);
+            print_text(&text, self.color);
+        }
+
+        Ok(())
+    }
+
+    /// Sets every enabled output to the largest resolution that all of them support.
+    pub async fn safe_mode(&mut self, test: bool) -> Result<(), Box<dyn std::error::Error>> {
+        self.dispatch_until_manager_done().await?;
+
+        let mut heads = sorted_heads(&self.context)
+            .into_iter()
+            .filter(|head| head.enabled && !head.modes.is_empty())
+            .collect::<Vec<_>>();
+
+        // Never leave the user without a display to recover from.
+        if heads.is_empty() {
+            heads.extend(
+                sorted_heads(&self.context)
+                    .into_iter()
+                    .find(|head| !head.modes.is_empty()),
+            );
+        }
+
+        if heads.is_empty() {
+            return Err("no output reports any modes".into());
+        }
+
+        let resolutions = |head: &OutputHead| {
+            head.modes
+                .values()
+                .map(|mode| (mode.width, mode.height))
+                .collect::<BTreeSet<_>>()
+        };
+
+        let common = heads
+            .iter()
+            .map(|head| resolutions(head))
+            .reduce(|common, other| &common & &other)
+            .unwrap_or_default()
+            .into_iter()
+            .max_by_key(|&(width, height)| (width * height, width));
+
+        let mut x = 0;
+        let mut plan = Vec::with_capacity(heads.len());
+
+        for head in heads {
+            let mode = match common {
+                Some((width, height)) => head
+                    .modes
+                    .values()
+                    .filter(|mode| mode.width == width && mode.height == height)
+                    .min_by_key(|mode| (mode.refresh - 60_000).abs()),
+                // Without a common resolution, fall back to the lowest mode of each.
+                None => head
+                    .modes
+                    .values()
+                    .min_by_key(|mode| (mode.width * mode.height, (mode.refresh - 60_000).abs())),
+            };
+
+            let Some(mode) = mode else {
+                continue;
+            };
+
+            plan.push((
+                head.name.clone(),
+                HeadConfiguration {
+                    size: Some((mode.width as u32, mode.height as u32)),
+                    refresh: Some(mode.refresh as f32 / 1000.0),
+                    pos: Some((x, 0)),
+                    scale: Some(1.0),
+                    transform: Some(WlTransform::Normal),
+                    ..Default::default()
+                },
+            ));
+
+            println!(
+                "{}: {}x{} @ {}.{:03} Hz at {x},0",
+                head.name,
+                mode.width,
+                mode.height,
+                mode.refresh / 1000,
+                mode.refresh % 1000
+            );
+
+            x += mode.width;
+        }
+
+        let mut config = self.context.create_output_config()?;
+        for (name, head_config) in plan {
+            config.enable_head(&name, Some(head_config))?;
+        }
+
+        submit(config, test)?;
+
+        self.receive_config_messages().await
+    }
+
+    /// Applies the best modes of the outputs whose combined bandwidth is within `budget`.
+    pub async fn fit(
+        &mut self,
+        outputs: &[String],
+        budget: f64,
+        bpp: u32,
+        test: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if !(budget.is_finite() && budget > 0.0) {
+            return Err(format!("invalid bandwidth: {budget}").into());
+        }
+
+        self.dispatch_until_manager_done().await?;
+
+        let bandwidth = |(width, height, refresh): (i32, i32, i32)| {
+            f64::from(width) * f64::from(height) * f64::from(refresh) / 1000.0 * f64::from(bpp)
+                / 1_000_000.0
+        };
+
+        // Width, height and refresh rate of the modes of each output, from the most to the
+        // least demanding, with the index of the mode currently chosen.
+        let mut plan: Vec<(String, Vec<(i32, i32, i32)>, usize)> =
+            Vec::with_capacity(outputs.len());
+        for output in outputs {
+            let output = resolve_output(&self.context, output)?;
+            if plan.iter().any(|(name, _, _)| *name == output) {
+                return Err(format!("{output} is listed more than once").into());
+            }
+
+            let head = self
+                .context
+                .output_heads
+                .values()
+                .find(|head| head.name == output)
+                .ok_or_else(|| CliError::UnknownOutput(output.clone()))?;
+
+            let mut modes = head
+                .modes
+                .values()
+                .map(|mode| (mode.width, mode.height, mode.refresh))
+                .collect::<Vec<_>>();
+            if modes.is_empty() {
+                return Err(format!("{output} reports no modes").into());
+            }
+
+            modes.sort_by_key(|&(width, height, refresh)| {
+                std::cmp::Reverse((width * height, refresh))
+            });
+
+            plan.push((output, modes, 0));
+        }
+
+        let total = |plan: &[(String, Vec<(i32, i32, i32)>, usize)]| {
+            plan.iter()
+                .map(|(_, modes, chosen)| bandwidth(modes[*chosen]))
+                .sum::<f64>()
+        };
+
+        while total(&plan) > budget {
+            // Step down the most demanding output that has a less demanding mode left.
+            let next = plan
+                .iter()
+                .enumerate()
+                .filter_map(|(index, (_, modes, chosen))| {
+                    let current = bandwidth(modes[*chosen]);
+                    let lower = (*chosen + 1..modes.len())
+                        .find(|&lower| bandwidth(modes[lower]) < current)?;
+                    Some((index, lower, current))
+                })
+                .max_by(|(_, _, a), (_, _, b)| a.total_cmp(b));
+
+            let Some((index, lower, _)) = next else {
+                return Err(format!(
+                    "no combination of modes fits in {budget} Mbps; the least demanding needs \
+                     {:.0} Mbps",
+                    total(&plan)
+                )
+                .into());
+            };
+
+            plan[index].2 = lower;
+        }
+
+        let mut config = self.context.create_output_config()?;
+        for (name, modes, chosen) in &plan {
+            let (width, height, refresh) = modes[*chosen];
+            println!(
+                "{name}: {width}x{height} @ {}.{:03} Hz, {:.0} Mbps",
+                refresh / 1000,
+                refresh % 1000,
+                bandwidth(modes[*chosen])
+            );
+
+            config.enable_head(
+                name,
+                Some(HeadConfiguration {
+                    size: Some((width as u32, height as u32)),
+                    refresh: Some(refresh as f32 / 1000.0),
+                    ..Default::default()
+                }),
+            )?;
+        }
+        println!("total: {:.0} of {budget} Mbps", total(&plan));
+
+        submit(config, test)?;
+
+        self.receive_config_messages().await
+    }
+
+    /// Applies the scale which brings the output closest to the target DPI.
+    pub async fn scale_to_dpi(
+        &mut self,
+        output: &str,
+        target_dpi: f64,
+        test: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if !(target_dpi.is_finite() && target_dpi > 0.0) {
+            return Err(format!("invalid target DPI: {target_dpi}").into());
+        }
+
+        self.dispatch_until_manager_done().await?;
+        let output = resolve_output(&self.context, output)?;
+
+        let head = self
+            .context
+            .output_heads
+            .values()
+            .find(|head| head.name == output)
+            .ok_or_else(|| CliError::UnknownOutput(output.to_string()))?;
+
+        let mode = head
+            .current()
+            .ok_or_else(|| format!("{output} has no current mode"))?;
+
+        if head.physical_width <= 0 || head.physical_height <= 0 {
+            return Err(format!(
+                "{output} does not report its physical size, so its DPI is unknown"
+            )
+            .into());
+        }
+
+        // Physical size is reported in the same orientation as the modes.
+        let dpi = (f64::from(mode.width) / f64::from(head.physical_width)
+            + f64::from(mode.height) / f64::from(head.physical_height))
+            / 2.0
+            * 25.4;
+
+        // Rounded to the quarter steps offered by COSMIC's display settings.
+        let scale = ((dpi / target_dpi) * 4.0).round() / 4.0;
+        let scale = scale.clamp(*validate::SCALE_RANGE.start(), *validate::SCALE_RANGE.end());
+
+        let (width, height) = head_transform(head).apply_to_size((mode.width, mode.height));
+
+        println!(
+            "{output}: {dpi:.0} DPI; scale {}% gives {:.0} DPI at {}x{}",
+            (scale * 100.0).round() as i32,
+            dpi / scale,
+            (f64::from(width) / scale).round() as i32,
+            (f64::from(height) / scale).round() as i32,
+        );
+
+        let mode = Mode {
+            scale: Some(scale),
+            test,
+            ..Mode::for_head(head, mode)
+        };
+
+        self.apply_mode(mode).await
+    }
+
+    /// Gives the output the logical size that `factor` would give its preferred mode,
+    /// using the mode and whole number scale that come closest to it.
+    pub async fn integer_scale(
+        &mut self,
+        output: &str,
+        factor: f64,
+        test: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if !(factor.is_finite() && validate::SCALE_RANGE.contains(&factor)) {
+            return Err(format!("invalid scale factor: {factor}").into());
+        }
+
+        self.dispatch_until_manager_done().await?;
+        let output = resolve_output(&self.context, output)?;
+
+        let head = self
+            .context
+            .output_heads
+            .values()
+            .find(|head| head.name == output)
+            .ok_or_else(|| CliError::UnknownOutput(output.to_string()))?;
+
+        let native = head
+            .preferred()
+            .ok_or_else(|| format!("{output} reports no modes"))?;
+
+        let target = (
+            f64::from(native.width) / factor,
+            f64::from(native.height) / factor,
+        );
+
+        // Prefer the closest logical size, then the most pixels, then the fastest refresh.
+        let integer_scales = 1..=validate::SCALE_RANGE.end().floor() as i32;
+        let (mode, scale) = head
+            .modes
+            .values()
+            .flat_map(|mode| integer_scales.clone().map(move |scale| (mode, scale)))
+            .min_by(|(a, a_scale), (b, b_scale)| {
+                let error = |mode: &OutputMode, scale: i32| {
+                    (f64::from(mode.width) / f64::from(scale) - target.0).abs()
+                        + (f64::from(mode.height) / f64::from(scale) - target.1).abs()
+                };
+
+                error(a, *a_scale)
+                    .total_cmp(&error(b, *b_scale))
+                    .then_with(|| (b.width * b.height).cmp(&(a.width * a.height)))
+                    .then_with(|| b.refresh.cmp(&a.refresh))
+            })
+            .ok_or_else(|| format!("{output} reports no modes"))?;
+
+        println!(
+            "{output}: {}x{} @ {}.{:03} Hz at scale {}% gives {}x{}, for {:.0}x{:.0} at {}%",
+            mode.width,
+            mode.height,
+            mode.refresh / 1000,
+            mode.refresh % 1000,
+            scale * 100,
+            mode.width / scale,
+            mode.height / scale,
+            target.0,
+            target.1,
+            (factor * 100.0).round() as i32,
+        );
+
+        let mode = Mode {
+            scale: Some(f64::from(scale)),
+            test,
+            ..Mode::for_head(head, mode)
+        };
+
+        self.apply_mode(mode).await
+    }
+
+    pub async fn mode(&mut self, mut mode: Mode) -> Result<(), Box<dyn std::error::Error>> {
+        self.dispatch_until_manager_done().await?;
+        mode.output = resolve_output(&self.context, &mode.output)?;
+
+        if (mode.pos_x.is_some() || mode.pos_y.is_some()) && !mode.allow_overlap {
+            if let Some(head) = self
+                .context
+                .output_heads
+                .values()
+                .find(|head| head.name == mode.output)
+            {
+                let (width, height) = requested_logical_size(head, &mode);
+                check_overlap(
+                    &self.context,
+                    &mode.output,
+                    mode.pos_x.unwrap_or_default(),
+                    mode.pos_y.unwrap_or_default(),
+                    width,
+                    height,
+                )?;
+            }
+        }
+
+        if mode.keep_center {
+            if let Some((x, y)) = centered_position(&self.context, &mode) {
+                (mode.pos_x, mode.pos_y) = (Some(x), Some(y));
+            }
+        }
+
+        let before = mode.emit_remap.then(|| layout(&self.context));
+        self.apply_mode(mode).await?;
+
+        if let Some(mut before) = before {
+            // Process the events describing the applied layout before reading it.
+            self.event_queue.roundtrip(&mut self.context)?;
+            let mut after = layout(&self.context);
+
+            let mut names = before
+                .keys()
+                .chain(after.keys())
+                .cloned()
+                .collect::<Vec<_>>();
+            names.sort_unstable();
+            names.dedup();
+
+            json::print(json::Remap {
+                outputs: names
+                    .into_iter()
+                    .map(|name| json::OutputRemap {
+                        before: before.remove(&name),
+                        after: after.remove(&name),
+                        name,
+                    })
+                    .collect(),
+            })?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn apply_mode(&mut self, mode: Mode) -> Result<(), Box<dyn std::error::Error>> {
+        // Without the COSMIC extension, automatic adaptive sync is enabled instead.
+        if mode.adaptive_sync == Some(AdaptiveSyncState::Auto)
+            && !mode.strict
+            && !self.context.cosmic_capabilities.automatic_adaptive_sync
+        {
+            eprintln!(
+                "warning: {} can't leave adaptive sync to this compositor; enabling it instead",
+                mode.output
+            );
+        }
+
+        if let Some(scale) = mode.scale.filter(|_| self.verbose) {
+            fractional_scale_note(&mode.output, mode.width, mode.height, scale);
+            xwayland_scale_note(&mode.output, scale);
+        }
+
+        if let Some(retries) = mode.retry_vrr.filter(|_| mode.adaptive_sync.is_some()) {
+            self.set_mode_retrying_vrr(&mode, retries).await?;
+        } else {
+            set_mode(&mut self.context, &mode)?;
+            self.receive_config_messages().await?;
+        }
+
+        self.auto_correct_offsets(&mode.output, mode.test).await
+    }
+
+    /// Applies a mode which changes adaptive sync, retrying with backoff while the
+    /// compositor rejects it, and finally applying the mode without the adaptive sync
+    /// change. Reports the adaptive sync state that the output ended up with.
+    async fn set_mode_retrying_vrr(
+        &mut self,
+        mode: &Mode,
+        retries: u32,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut delay = VRR_RETRY_DELAY;
+        let mut applied = false;
+
+        for attempt in 0..=retries {
+            if attempt > 0 {
+                eprintln!(
+                    "adaptive sync change on {} failed; retrying in {} ms ({attempt}/{retries})",
+                    mode.output,
+                    delay.as_millis()
+                );
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+
+            set_mode(&mut self.context, mode)?;
+            let Err(why) = self.receive_config_messages().await else {
+                applied = true;
+                break;
+            };
+
+            if !matches!(
+                why.downcast_ref::<CliError>(),
+                Some(CliError::ConfigurationFailed)
+            ) {
+                return Err(why);
+            }
+        }
+
+        if !applied {
+            eprintln!(
+                "adaptive sync change on {} failed {} time(s); applying without it",
+                mode.output,
+                retries + 1
+            );
+
+            let without_vrr = Mode {
+                adaptive_sync: None,
+                ..mode.clone()
+            };
+            set_mode(&mut self.context, &without_vrr)?;
+            self.receive_config_messages().await?;
+        }
+
+        // Process the events describing the applied state before reading it.
+        self.event_queue.roundtrip(&mut self.context)?;
+        let state = self
+            .context
+            .output_heads
+            .values()
+            .find(|head| head.name == mode.output)
+            .and_then(|head| head.adaptive_sync)
+            .and_then(|sync| AdaptiveSyncState::try_from(sync).ok());
+
+        match state {
+            Some(state) => println!("{}: adaptive sync {state}", mode.output),
+            None => println!("{}: adaptive sync state unknown", mode.output),
+        }
+
+        Ok(())
+    }
+
+    /// Changes the refresh rate at the current resolution, and reverts it after
+    /// `duration` if given.
+    pub async fn refresh(
+        &mut self,
+        output: &str,
+        hz: f32,
+        duration: Option<Duration>,
+        test: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.dispatch_until_manager_done().await?;
+        let output = resolve_output(&self.context, output)?;
+
+        let head = self
+            .context
+            .output_heads
+            .values()
+            .find(|head| head.name == output)
+            .ok_or_else(|| CliError::UnknownOutput(output.to_string()))?;
+
+        let current = head
+            .current()
+            .ok_or_else(|| format!("{output} has no current mode"))?;
+        let (width, height) = (current.width, current.height);
+        let previous = current.refresh;
+
+        if head
+            .find_mode(width, height, (hz * 1000.0).round() as i32)
+            .is_none()
+        {
+            let available = head
+                .modes
+                .values()
+                .filter(|mode| mode.width == width && mode.height == height)
+                .map(|mode| format!("{}.{:03}", mode.refresh / 1000, mode.refresh % 1000))
+                .collect::<Vec<_>>();
+
+            return Err(format!(
+                "no {width}x{height} mode of {output} within 0.5 Hz of {hz} Hz; available: {} Hz",
+                available.join(", ")
+            )
+            .into());
+        }
+
+        let mode = Mode {
+            refresh: Some(Refresh::Hz(hz)),
+            test,
+            ..Mode::new(output, width, height)
+        };
+
+        self.apply_mode(mode.clone()).await?;
+
+        let Some(duration) = duration else {
+            return Ok(());
+        };
+
+        println!(
+            "{}: {hz} Hz for {:.1} s; press Ctrl-C to revert early",
+            mode.output,
+            duration.as_secs_f32()
+        );
+
+        tokio::select! {
+            () = tokio::time::sleep(duration) => (),
+            result = tokio::signal::ctrl_c() => result?,
+        }
+
+        // Catch up with any changes made while waiting before configuring again.
+        self.event_queue.roundtrip(&mut self.context)?;
+
+        println!(
+            "{}: reverting to {}.{:03} Hz",
+            mode.output,
+            previous / 1000,
+            previous % 1000
+        );
+
+        self.apply_mode(Mode {
+            refresh: Some(Refresh::Hz(previous as f32 / 1000.0)),
+            ..mode
+        })
+        .await
+    }
+
+    pub async fn refresh_modeset(
+        &mut self,
+        output: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.dispatch_until_manager_done().await?;
+        let output = resolve_output(&self.context, output)?;
+        refresh_modeset(&mut self.context, &output)?;
+        self.receive_config_messages().await
+    }
+}
+
+fn set_mode(context: &mut Context, args: &Mode) -> Result<(), Box<dyn std::error::Error>> {
+    let head = context
+        .output_heads
+        .values()
+        .find(|output| output.name == args.output);
+
+    if let Some((head, transform)) = head.zip(args.transform) {
+        transform_note(head, args.width, args.height, transform);
+    }
+
+    let mirroring = head.and_then(|head| head.mirroring.clone());
+    let mut head_config = args.to_head_config();
+
+    // `max` and `min` stand for the refresh rates of the modes of the requested size.
+    if let Some(extreme @ (Refresh::Max | Refresh::Min)) = args.refresh {
+        let head = head.ok_or_else(|| CliError::UnknownOutput(args.output.clone()))?;
+        let mut rates = head
+            .modes
+            .values()
+            .filter(|mode| mode.width == args.width && mode.height == args.height)
+            .map(|mode| mode.refresh);
+
+        let refresh = if extreme == Refresh::Max {
+            rates.max()
+        } else {
+            rates.min()
+        }
+        .ok_or_else(|| CliError::ModeNotFound {
+            output: args.output.clone(),
+            alternatives: mode_alternatives(head.modes.values(), args.width, args.height),
+        })?;
+
+        head_config.refresh = Some(refresh as f32 / 1000.0);
+    }
+
+    let mut config = context.create_output_config()?;
+
+    let result = if let Some(mirroring_from) = mirroring.filter(|_| head_config.pos.is_none()) {
+        config.mirror_head(&args.output, &mirroring_from, Some(head_config))
+    } else {
+        config.enable_head(&args.output, Some(head_config))
+    };
+
+    if let Err(ConfigurationError::ModeNotFound) = result {
+        if let Some(head) = context
+            .output_heads
+            .values()
+            .find(|head| head.name == args.output)
+        {
+            return Err(CliError::ModeNotFound {
+                output: args.output.clone(),
+                alternatives: mode_alternatives(head.modes.values(), args.width, args.height),
+            }
+            .into());
+        }
+    }
+
+    result?;
+    submit(config, args.test)?;
+
+    Ok(())
+}
+
+/// The mode of the output that a mode of a layout stands for.
+///
+/// Several modes may share a resolution, so the refresh rate selects the live mode, to
+/// the millihertz where it matches exactly.
+pub fn live_mode<'a, M: ModeTiming>(
+    modes: impl IntoIterator<Item = &'a M>,
+    mode: &cosmic_randr_shell::Mode,
+) -> Option<&'a M> {
+    let size = (mode.size.0 as i32, mode.size.1 as i32);
+    select_mode(modes, size, mode.refresh_rate as i32)
+}
+
+/// Describes the closest modes to `width`x`height` that the output supports: the refresh
+/// rates at that size, or the sizes nearest to it if it has no mode of that size.
+pub fn mode_alternatives<'a, M: ModeTiming + 'a>(
+    modes: impl Iterator<Item = &'a M> + Clone,
+    width: i32,
+    height: i32,
+) -> String {
+    let mut rates = modes
+        .clone()
+        .filter(|mode| mode.size() == (width, height))
+        .map(ModeTiming::refresh)
+        .collect::<Vec<_>>();
+
+    if !rates.is_empty() {
+        rates.sort_unstable_by(|a, b| b.cmp(a));
+        rates.dedup();
+
+        let rates = rates
+            .iter()
+            .map(|refresh| format!("{}.{:03}", refresh / 1000, refresh % 1000))
+            .collect::<Vec<_>>();
+
+        return format!("{width}x{height} supports: {} Hz", rates.join(", "));
+    }
+
+    let mut sizes = modes.map(ModeTiming::size).collect::<Vec<_>>();
+    sizes.sort_unstable_by_key(|&(w, h)| {
+        (
+            (w - width).abs() + (h - height).abs(),
+            std::cmp::Reverse((w, h)),
+        )
+    });
+    sizes.dedup();
+
+    let sizes = sizes
+        .iter()
+        .take(5)
+        .map(|(w, h)| format!("{w}x{h}"))
+        .collect::<Vec<_>>();
+
+    format!("closest sizes to {width}x{height}: {}", sizes.join(", "))
+}
+
+fn refresh_modeset(context: &mut Context, output: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let head = context
+        .output_heads
+        .values()
+        .find(|head| head.name == output)
+        .ok_or_else(|| CliError::UnknownOutput(output.to_string()))?;
+
+    if !head.enabled {
+        return Err(format!("{output} is disabled").into());
+    }
+
+    let mode = head
+        .current_mode
+        .as_ref()
+        .and_then(|id| head.modes.get(id))
+        .ok_or_else(|| format!("{output} has no current mode"))?;
+
+    // Explicitly requesting the mode it already has makes the compositor drive it again.
+    let head_config = HeadConfiguration {
+        size: Some((mode.width as u32, mode.height as u32)),
+        refresh: Some(mode.refresh as f32 / 1000.0),
+        ..Default::default()
+    };
+    let mirroring = head.mirroring.clone();
+
+    let mut config = context.create_output_config()?;
+
+    if let Some(mirroring_from) = mirroring {
+        config.mirror_head(output, &mirroring_from, Some(head_config))?;
+    } else {
+        config.enable_head(output, Some(head_config))?;
+    }
+
+    submit(config, false)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Modes of a 1440p panel, with several refresh rates at its native resolution.
+    const MODES: [(i32, i32, i32); 4] = [
+        (2560, 1440, 59_951),
+        (2560, 1440, 120_000),
+        (2560, 1440, 143_999),
+        (1920, 1080, 60_000),
+    ];
+
+    fn layout_mode(width: u32, height: u32, refresh_rate: u32) -> cosmic_randr_shell::Mode {
+        cosmic_randr_shell::Mode {
+            size: (width, height),
+            refresh_rate,
+            preferred: false,
+        }
+    }
+
+    #[test]
+    fn live_mode_selects_each_refresh_rate() {
+        for &(width, height, refresh) in &MODES {
+            let mode = layout_mode(width as u32, height as u32, refresh as u32);
+            assert_eq!(live_mode(&MODES, &mode), Some(&(width, height, refresh)));
+        }
+    }
+
+    #[test]
+    fn live_mode_rounds_to_nearest_refresh_rate() {
+        let mode = layout_mode(2560, 1440, 144_000);
+        assert_eq!(live_mode(&MODES, &mode), Some(&(2560, 1440, 143_999)));
+
+        let mode = layout_mode(2560, 1440, 60_000);
+        assert_eq!(live_mode(&MODES, &mode), Some(&(2560, 1440, 59_951)));
+    }
+
+    #[test]
+    fn live_mode_without_refresh_rate_at_resolution() {
+        let mode = layout_mode(2560, 1440, 100_000);
+        assert_eq!(live_mode(&MODES, &mode), None);
+
+        let mode = layout_mode(1920, 1080, 143_999);
+        assert_eq!(live_mode(&MODES, &mode), None);
+    }
+
+    #[test]
+    fn mode_alternatives_at_size() {
+        assert_eq!(
+            mode_alternatives(MODES.iter(), 2560, 1440),
+            "2560x1440 supports: 143.999, 120.000, 59.951 Hz"
+        );
+    }
+
+    #[test]
+    fn mode_alternatives_without_size() {
+        assert_eq!(
+            mode_alternatives(MODES.iter(), 3840, 2160),
+            "closest sizes to 3840x2160: 2560x1440, 1920x1080"
+        );
+    }
+}
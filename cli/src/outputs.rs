@@ -0,0 +1,191 @@
+// Copyright 2023 System76 <info@system76.com>
+// SPDX-License-Identifier: MPL-2.0
+
+//! Selection of the outputs that commands act on.
+
+use crate::OutputSort;
+use clap_complete::engine::CompletionCandidate;
+use cosmic_randr::output_head::OutputHead;
+use cosmic_randr::Context;
+use std::collections::BTreeSet;
+
+pub fn context_has_output(context: &Context, output: &str) -> bool {
+    context
+        .output_heads
+        .values()
+        .any(|head| head.name == output)
+}
+
+/// Offers the connector names of the outputs for completion, when a compositor is
+/// running to report them.
+pub fn output_candidates() -> Vec<CompletionCandidate> {
+    let Ok(outputs) =
+        cosmic_randr::BlockingContext::connect().and_then(|mut context| context.list_outputs())
+    else {
+        return Vec::new();
+    };
+
+    outputs
+        .into_iter()
+        .map(|head| CompletionCandidate::new(head.name).help(Some(head.model.into())))
+        .collect()
+}
+
+/// Names of the outputs that are currently connected.
+pub fn connected_outputs(context: &Context) -> BTreeSet<String> {
+    context
+        .output_heads
+        .values()
+        .map(|head| head.name.clone())
+        .collect()
+}
+
+/// Output heads in the deterministic order used by `list`.
+pub fn sorted_heads(context: &Context) -> Vec<&OutputHead> {
+    let mut heads = context.output_heads.values().collect::<Vec<_>>();
+    heads.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+    heads
+}
+
+/// Output heads in the order chosen with `list --sort-outputs`.
+pub fn sorted_heads_by(context: &Context, sort: OutputSort) -> Vec<&OutputHead> {
+    let mut heads = sorted_heads(context);
+    sort.sort(&mut heads);
+    heads
+}
+
+/// Resolves an output selector to the name of a connected output.
+///
+/// `@N` selects the Nth output in `list` order. This is a position in the list rather
+/// than a stable identifier, so it may refer to a different output after a hotplug.
+///
+/// `id:ID` selects the output with that stable identifier, as printed by `list`.
+///
+/// A selector that isn't the name of a connector may instead be the serial number, the
+/// model, or the make and model of a display, such as `Dell U2720Q`, which identify it
+/// on whichever connector it is plugged into.
+pub fn resolve_output(
+    context: &Context,
+    selector: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    select_output(&sorted_heads(context), selector)
+}
+
+/// Same as [`resolve_output`], among output heads in `list` order.
+fn select_output(
+    heads: &[&OutputHead],
+    selector: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    if let Some(id) = selector.strip_prefix("id:") {
+        return heads
+            .iter()
+            .find(|head| head.stable_id() == id)
+            .map(|head| head.name.clone())
+            .ok_or_else(|| format!("no output has the id {id}").into());
+    }
+
+    let Some(index) = selector.strip_prefix('@') else {
+        return resolve_output_by_identity(heads, selector);
+    };
+
+    let index = index
+        .parse::<usize>()
+        .map_err(|_| format!("invalid output index: {selector}"))?;
+
+    heads
+        .get(index)
+        .map(|head| head.name.clone())
+        .ok_or_else(|| format!("no output at index {index}").into())
+}
+
+fn resolve_output_by_identity(
+    heads: &[&OutputHead],
+    selector: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    if heads.iter().any(|head| head.name == selector) {
+        return Ok(selector.to_owned());
+    }
+
+    let matches = heads
+        .iter()
+        .filter(|head| {
+            (!head.serial_number.is_empty() && head.serial_number == selector)
+                || (!head.model.is_empty() && head.model == selector)
+                || (!head.make.is_empty() && format!("{} {}", head.make, head.model) == selector)
+        })
+        .map(|head| head.name.clone())
+        .collect::<Vec<_>>();
+
+    match matches.as_slice() {
+        // Left for the command to report as an unknown output.
+        [] => Ok(selector.to_owned()),
+        [name] => Ok(name.clone()),
+        names => Err(format!(
+            "{selector} matches several outputs: {}; select one by connector name",
+            names.join(", ")
+        )
+        .into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn heads() -> [OutputHead; 3] {
+        let head = |name, make: &str, model: &str, serial_number: &str| {
+            let mut head = OutputHead::inert(name);
+            head.make = make.to_owned();
+            head.model = model.to_owned();
+            head.serial_number = serial_number.to_owned();
+            head
+        };
+
+        [
+            head("DP-1", "Dell Inc.", "DELL U2720Q", "ABC123"),
+            head("DP-2", "Dell Inc.", "DELL U2720Q", "DEF456"),
+            head("HDMI-A-1", "LG Electronics", "LG TV", ""),
+        ]
+    }
+
+    fn select(selector: &str) -> Result<String, String> {
+        let heads = heads();
+        let heads = heads.iter().collect::<Vec<_>>();
+        select_output(&heads, selector).map_err(|why| why.to_string())
+    }
+
+    #[test]
+    fn select_by_index() {
+        assert_eq!(select("@0").unwrap(), "DP-1");
+        assert_eq!(select("@2").unwrap(), "HDMI-A-1");
+        assert_eq!(select("@3").unwrap_err(), "no output at index 3");
+        assert_eq!(select("@one").unwrap_err(), "invalid output index: @one");
+    }
+
+    #[test]
+    fn select_by_stable_id() {
+        assert_eq!(select("id:dell-inc-dell-u2720q-def456").unwrap(), "DP-2");
+        assert_eq!(
+            select("id:lg-electronics-lg-tv-hdmi-a-1").unwrap(),
+            "HDMI-A-1"
+        );
+        assert_eq!(
+            select("id:dell-inc-dell-u2720q").unwrap_err(),
+            "no output has the id dell-inc-dell-u2720q"
+        );
+    }
+
+    #[test]
+    fn select_by_identity() {
+        assert_eq!(select("DP-2").unwrap(), "DP-2");
+        assert_eq!(select("DEF456").unwrap(), "DP-2");
+        assert_eq!(select("LG TV").unwrap(), "HDMI-A-1");
+        assert_eq!(select("LG Electronics LG TV").unwrap(), "HDMI-A-1");
+        assert_eq!(
+            select("DELL U2720Q").unwrap_err(),
+            "DELL U2720Q matches several outputs: DP-1, DP-2; select one by connector name"
+        );
+        // Left for the command to report as unknown.
+        assert_eq!(select("eDP-1").unwrap(), "eDP-1");
+    }
+}
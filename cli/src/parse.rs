@@ -0,0 +1,151 @@
+// Copyright 2023 System76 <info@system76.com>
+// SPDX-License-Identifier: MPL-2.0
+
+//! Parsers of command line values.
+
+use crate::{Refresh, MAX_REFRESH_HZ};
+use std::time::Duration;
+
+/// Parses a refresh rate in Hz, catching rates that were given in mHz instead.
+pub fn parse_refresh(value: &str) -> Result<f32, String> {
+    let hz = value
+        .parse::<f32>()
+        .map_err(|_| format!("`{value}` is not a refresh rate in Hz, such as 144 or 59.94"))?;
+
+    if !(hz.is_finite() && hz > 0.0) {
+        return Err(format!(
+            "`{value}` is not a positive refresh rate in Hz, such as 144 or 59.94"
+        ));
+    }
+
+    if hz > MAX_REFRESH_HZ {
+        return Err(format!(
+            "{value} Hz is not a plausible refresh rate; rates are given in Hz rather than mHz, so you probably meant {}",
+            hz / 1000.0
+        ));
+    }
+
+    Ok(hz)
+}
+
+/// Parses a scale given either as a factor, such as `1.5`, or as a percentage, such as
+/// `150%` as printed by `list`.
+pub fn parse_scale(value: &str) -> Result<f64, String> {
+    let (number, divisor) = match value.strip_suffix('%') {
+        Some(percent) => (percent.trim_end(), 100.0),
+        None => (value, 1.0),
+    };
+
+    match number.parse::<f64>() {
+        Ok(scale) if scale.is_finite() && scale > 0.0 => Ok(scale / divisor),
+        _ => Err(format!(
+            "`{value}` is not a positive scale, such as 1.5 or 150%"
+        )),
+    }
+}
+
+pub fn parse_mode_refresh(value: &str) -> Result<Refresh, String> {
+    if value.eq_ignore_ascii_case("max") {
+        Ok(Refresh::Max)
+    } else if value.eq_ignore_ascii_case("min") {
+        Ok(Refresh::Min)
+    } else {
+        parse_refresh(value).map(Refresh::Hz)
+    }
+}
+
+/// Parses a duration such as `30s`, `500ms` or `2m`, taken as seconds without a unit.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let invalid = || format!("`{value}` is not a duration, such as 30s, 500ms or 2m");
+
+    let (number, unit) = value
+        .find(|c: char| c.is_ascii_alphabetic())
+        .map_or((value, "s"), |index| value.split_at(index));
+    let number = number.trim().parse::<u64>().map_err(|_| invalid())?;
+
+    match unit {
+        "ms" => Ok(Duration::from_millis(number)),
+        "s" => Ok(Duration::from_secs(number)),
+        "m" => Ok(Duration::from_secs(number.saturating_mul(60))),
+        _ => Err(invalid()),
+    }
+}
+
+/// Prefix of the shell variables describing an output, with every character of the
+/// connector name that is not valid in a variable name replaced by `_`.
+pub fn env_prefix(name: &str) -> String {
+    let name = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+
+    format!("COSMIC_RANDR_{name}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refresh_in_hz() {
+        assert_eq!(parse_refresh("144"), Ok(144.0));
+        assert_eq!(parse_refresh("59.94"), Ok(59.94));
+    }
+
+    #[test]
+    fn refresh_in_mhz() {
+        let why = parse_refresh("143999").unwrap_err();
+        assert!(why.contains("you probably meant 143.999"), "{why}");
+    }
+
+    #[test]
+    fn refresh_not_positive() {
+        for value in ["0", "-60", "NaN", "inf", "fast"] {
+            assert!(parse_refresh(value).is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn mode_refresh_keywords() {
+        assert_eq!(parse_mode_refresh("max"), Ok(Refresh::Max));
+        assert_eq!(parse_mode_refresh("MIN"), Ok(Refresh::Min));
+        assert_eq!(parse_mode_refresh("120"), Ok(Refresh::Hz(120.0)));
+        assert!(parse_mode_refresh("maximum").is_err());
+    }
+
+    #[test]
+    fn scale_as_factor_or_percentage() {
+        assert_eq!(parse_scale("1.5"), Ok(1.5));
+        assert_eq!(parse_scale("150%"), Ok(1.5));
+        assert_eq!(parse_scale("125 %"), Ok(1.25));
+    }
+
+    #[test]
+    fn scale_not_positive() {
+        for value in ["0", "-1.5", "0%", "%", "inf", "large"] {
+            assert!(parse_scale(value).is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn duration_units() {
+        assert_eq!(parse_duration("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("2m"), Ok(Duration::from_secs(120)));
+        assert_eq!(parse_duration("2 m"), Ok(Duration::from_secs(120)));
+    }
+
+    #[test]
+    fn duration_invalid() {
+        for value in ["", "s", "-1s", "1.5s", "2h", "soon"] {
+            assert!(parse_duration(value).is_err(), "{value}");
+        }
+    }
+}
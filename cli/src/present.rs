@@ -0,0 +1,204 @@
+// Copyright 2023 System76 <info@system76.com>
+// SPDX-License-Identifier: MPL-2.0
+
+//! Presenting with `present toggle`, and extending with `extend`.
+
+use crate::error::CliError;
+use crate::geometry::{logical_rectangle, mode_logical_size};
+use crate::layout::parse_kdl_list;
+use crate::outputs::{connected_outputs, context_has_output, resolve_output, sorted_heads};
+use crate::{store, submit, test_passed, App, ModeSort};
+use cosmic_randr::context::HeadConfiguration;
+use kdl::KdlDocument;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// State of `present toggle`, kept between invocations.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct Presentation {
+    /// Extended layout to restore, in the KDL format of `list --kdl`, set while
+    /// presenting.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    layout: Option<String>,
+}
+
+const PRESENTATION_FILE: &str = "presentation.json";
+
+impl App {
+    /// Mirrors every display onto the primary, or restores the layout saved by doing so.
+    pub async fn present_toggle(
+        &mut self,
+        primary: Option<&str>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.dispatch_until_manager_done().await?;
+
+        let presentation: Presentation = store::load(PRESENTATION_FILE)?;
+
+        if let Some(layout) = presentation.layout {
+            // Only restore the saved layout while it describes the same set of outputs,
+            // as outputs missing from it would be left mirrored.
+            let list = parse_kdl_list(&layout)?;
+            let saved = list
+                .outputs
+                .values()
+                .map(|output| output.name.clone())
+                .collect::<BTreeSet<_>>();
+
+            if saved == connected_outputs(&self.context) {
+                self.submit_list(&list, &[], false).await?;
+                println!("restored the extended layout");
+            } else {
+                eprintln!("outputs changed while presenting; arranging them from left to right");
+                let enabled = sorted_heads(&self.context)
+                    .into_iter()
+                    .filter(|head| head.enabled)
+                    .map(|head| head.name.clone())
+                    .collect::<Vec<_>>();
+                self.extend_outputs(&enabled, false).await?;
+            }
+
+            // Under `--dry-run`, nothing was restored.
+            if !self.context.dry_run {
+                store::save(PRESENTATION_FILE, &Presentation::default())?;
+            }
+            return Ok(());
+        }
+
+        let primary = match primary {
+            Some(primary) => resolve_output(&self.context, primary)?,
+            None => self
+                .context
+                .output_heads
+                .values()
+                .filter(|head| head.enabled && head.mirroring.is_none())
+                .min_by_key(|head| (head.position_x, head.position_y, head.name.clone()))
+                .map(|head| head.name.clone())
+                .ok_or("no enabled output to present from")?,
+        };
+
+        if !context_has_output(&self.context, &primary) {
+            return Err(CliError::UnknownOutput(primary).into());
+        }
+
+        let targets = sorted_heads(&self.context)
+            .into_iter()
+            .filter(|head| head.enabled && head.name != primary)
+            .map(|head| head.name.clone())
+            .collect::<Vec<_>>();
+
+        if targets.is_empty() {
+            return Err(format!("no other enabled output to mirror {primary}").into());
+        }
+
+        self.sort_modes(ModeSort::Res, false);
+        let layout = KdlDocument::from(self.context.current_config()).to_string();
+
+        let mut config = self.context.create_output_config()?;
+        config.enable_head(&primary, None)?;
+        for name in &targets {
+            config.mirror_head(name, &primary, None)?;
+        }
+        submit(config, false)?;
+        self.receive_config_messages().await?;
+
+        if !self.context.dry_run {
+            store::save(
+                PRESENTATION_FILE,
+                &Presentation {
+                    layout: Some(layout),
+                },
+            )?;
+        }
+
+        println!("mirroring {primary} on: {}", targets.join(", "));
+        Ok(())
+    }
+
+    /// Enables every connected output, mirrored, disabled or not, and places them from
+    /// left to right, starting with those in `order`.
+    pub async fn extend(
+        &mut self,
+        order: &[String],
+        test: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.dispatch_until_manager_done().await?;
+
+        let mut names: Vec<String> = Vec::with_capacity(self.context.output_heads.len());
+        for output in order {
+            let output = resolve_output(&self.context, output)?;
+            if names.contains(&output) {
+                return Err(format!("{output} is listed more than once").into());
+            }
+            names.push(output);
+        }
+
+        for head in sorted_heads(&self.context) {
+            if !names.contains(&head.name) {
+                names.push(head.name.clone());
+            }
+        }
+
+        let placed = self.extend_outputs(&names, test).await?;
+
+        let prefix = if test { "would be " } else { "" };
+        println!("{prefix}extended from left to right: {}", placed.join(", "));
+        test_passed(test);
+        Ok(())
+    }
+
+    /// Extends the outputs, mirrored or not, placing them from left to right at the top
+    /// of the layout in the order given, and returns the names of those placed.
+    ///
+    /// Disabled outputs are enabled at their preferred mode, and outputs without any
+    /// mode are left out.
+    pub async fn extend_outputs(
+        &mut self,
+        names: &[String],
+        test: bool,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let mut x = 0.0_f32;
+        let mut plan = Vec::with_capacity(names.len());
+
+        for name in names {
+            let Some(head) = self
+                .context
+                .output_heads
+                .values()
+                .find(|head| head.name == *name)
+            else {
+                continue;
+            };
+
+            let mut head_config = HeadConfiguration {
+                pos: Some((x.round() as i32, 0)),
+                ..Default::default()
+            };
+
+            let width = if let Some(rectangle) = logical_rectangle(head).filter(|_| head.enabled) {
+                rectangle.width
+            } else if let Some(mode) = head.preferred() {
+                head_config.size = Some((mode.width as u32, mode.height as u32));
+                head_config.refresh = Some(mode.refresh as f32 / 1000.0);
+                mode_logical_size(head, mode).0
+            } else {
+                eprintln!("skipping {name}: output reports no modes");
+                continue;
+            };
+
+            plan.push((name.clone(), head_config));
+            x += width;
+        }
+
+        let mut config = self.context.create_output_config()?;
+        let mut placed = Vec::with_capacity(plan.len());
+        for (name, head_config) in plan {
+            config.enable_head(&name, Some(head_config))?;
+            placed.push(name);
+        }
+
+        submit(config, test)?;
+        self.receive_config_messages().await?;
+
+        Ok(placed)
+    }
+}
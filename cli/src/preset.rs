@@ -0,0 +1,128 @@
+// Copyright 2023 System76 <info@system76.com>
+// SPDX-License-Identifier: MPL-2.0
+
+//! Modes saved under a name with `mode-preset`.
+
+use crate::outputs::resolve_output;
+use crate::{store, App, Mode, ModePresetCommand, Refresh};
+use cosmic_randr::context::HeadConfiguration;
+use cosmic_randr::AdaptiveSyncStateExt;
+use cosmic_randr_shell::{AdaptiveSyncState, Transform};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+use wayland_client::protocol::wl_output::Transform as WlTransform;
+
+/// A mode saved under a name for a single output.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ModePreset {
+    pub width: i32,
+    pub height: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub adaptive_sync: Option<AdaptiveSyncState>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transform: Option<Transform>,
+}
+
+impl ModePreset {
+    pub fn to_head_config(&self) -> HeadConfiguration {
+        HeadConfiguration {
+            size: Some((self.width as u32, self.height as u32)),
+            refresh: self.refresh,
+            adaptive_sync: self.adaptive_sync.map(AdaptiveSyncStateExt::from),
+            scale: self.scale,
+            transform: self.transform.map(WlTransform::from),
+            ..Default::default()
+        }
+    }
+}
+
+/// Mode presets of each output, keyed by output name and then preset name.
+type ModePresets = BTreeMap<String, BTreeMap<String, ModePreset>>;
+
+const MODE_PRESETS_FILE: &str = "mode-presets.json";
+
+impl App {
+    pub async fn mode_preset(
+        &mut self,
+        command: ModePresetCommand,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match command {
+            ModePresetCommand::Save {
+                name,
+                output,
+                width,
+                height,
+                refresh,
+                adaptive_sync,
+                scale,
+                transform,
+            } => {
+                self.dispatch_until_manager_done().await?;
+                let output = resolve_output(&self.context, &output)?;
+
+                let mut presets = store::load::<ModePresets>(MODE_PRESETS_FILE)?;
+                presets.entry(output).or_default().insert(
+                    name,
+                    ModePreset {
+                        width,
+                        height,
+                        refresh,
+                        adaptive_sync,
+                        scale,
+                        transform,
+                    },
+                );
+                store::save(MODE_PRESETS_FILE, &presets)
+            }
+
+            ModePresetCommand::Apply { name, output, test } => {
+                self.dispatch_until_manager_done().await?;
+                let output = resolve_output(&self.context, &output)?;
+
+                let preset = store::load::<ModePresets>(MODE_PRESETS_FILE)?
+                    .get(&output)
+                    .and_then(|presets| presets.get(&name))
+                    .cloned()
+                    .ok_or_else(|| format!("no mode preset named {name} for {output}"))?;
+
+                self.apply_mode(Mode {
+                    refresh: preset.refresh.map(Refresh::Hz),
+                    adaptive_sync: preset.adaptive_sync,
+                    scale: preset.scale,
+                    test,
+                    transform: preset.transform,
+                    ..Mode::new(output, preset.width, preset.height)
+                })
+                .await
+            }
+
+            ModePresetCommand::List { output } => {
+                self.dispatch_until_manager_done().await?;
+                let output = resolve_output(&self.context, &output)?;
+
+                let presets = store::load::<ModePresets>(MODE_PRESETS_FILE)?;
+                let mut stdout = std::io::stdout().lock();
+
+                for (name, preset) in presets.get(&output).into_iter().flatten() {
+                    #[allow(clippy::ignored_unit_patterns)]
+                    let _res = fomat_macros::wite!(
+                        &mut stdout,
+                        (name) ": " (preset.width) "x" (preset.height)
+                        if let Some(refresh) = preset.refresh { " @ " (refresh) " Hz" }
+                        if let Some(scale) = preset.scale { " scale " ((scale * 100.0) as i32) "%" }
+                        if let Some(transform) = preset.transform { " " (transform) }
+                        if let Some(sync) = preset.adaptive_sync { " adaptive_sync=" (sync) }
+                        "\n"
+                    );
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
@@ -0,0 +1,123 @@
+// Copyright 2023 System76 <info@system76.com>
+// SPDX-License-Identifier: MPL-2.0
+
+//! Reports of the state of the outputs, for bug reports with `share` and `dump-state`.
+
+use crate::list::{list_text, strip_colors};
+use crate::outputs::sorted_heads;
+use crate::{json, App, ModeSort};
+use cosmic_randr::output_head::OutputHead;
+use std::fmt::Write as FmtWrite;
+use std::io::Write;
+
+impl App {
+    pub async fn dump_state(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.dispatch_until_manager_done().await?;
+        json::print(json::State::from(&self.context))
+    }
+
+    pub async fn share(&mut self, redact_serial: bool) -> Result<(), Box<dyn std::error::Error>> {
+        self.dispatch_until_manager_done().await?;
+        self.sort_modes(ModeSort::Res, false);
+
+        let mut report = String::new();
+        writeln!(report, "```text")?;
+        writeln!(report, "cosmic-randr {}", env!("CARGO_PKG_VERSION"))?;
+
+        let globals = json::State::from(&self.context).globals;
+        if globals.is_empty() {
+            writeln!(report, "protocols: none bound")?;
+        }
+        for global in globals {
+            writeln!(
+                report,
+                "protocol: {} version {}",
+                global.interface, global.version
+            )?;
+        }
+
+        if let Some(note) = self.context.cosmic_capabilities.partial_support() {
+            writeln!(report, "note: {note}")?;
+        }
+
+        report.push('\n');
+        // Rendered from copies of the outputs with their serial numbers replaced, so that
+        // nothing else that happens to contain a serial number is altered.
+        let heads = sorted_heads(&self.context)
+            .into_iter()
+            .map(|head| {
+                if redact_serial {
+                    redact_serial_number(head)
+                } else {
+                    head.clone()
+                }
+            })
+            .collect::<Vec<_>>();
+
+        report.push_str(&strip_colors(&list_text(&heads)));
+        report.push('\n');
+
+        for head in sorted_heads(&self.context) {
+            let capabilities = self
+                .context
+                .capabilities(head)
+                .entries()
+                .into_iter()
+                .filter(|(_, available)| *available)
+                .map(|(name, _)| name)
+                .collect::<Vec<_>>();
+
+            writeln!(
+                report,
+                "capabilities of {}: {}",
+                head.name,
+                capabilities.join(", ")
+            )?;
+        }
+
+        writeln!(report, "```")?;
+
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(report.as_bytes())?;
+        stdout.flush()?;
+        Ok(())
+    }
+}
+
+/// Copy of the output with its serial number, if it reports one, replaced by `redacted`.
+fn redact_serial_number(head: &OutputHead) -> OutputHead {
+    let mut redacted = head.clone();
+    if !redacted.serial_number.is_empty() {
+        redacted.serial_number = String::from("redacted");
+    }
+    redacted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacted_serial_number() {
+        // The serial number is part of the model, which must be left as it is.
+        let mut head = OutputHead::inert("DP-1");
+        head.make = "Dell Inc.".to_owned();
+        head.model = "U2723QE".to_owned();
+        head.serial_number = "27".to_owned();
+        let text = strip_colors(&list_text([&redact_serial_number(&head)]));
+
+        assert!(text.contains("Model: U2723QE"), "{text}");
+        assert!(text.contains("ID: dell-inc-u2723qe-redacted"), "{text}");
+        assert!(!text.contains("-27"), "{text}");
+    }
+
+    #[test]
+    fn redacted_without_serial_number() {
+        let mut head = OutputHead::inert("HDMI-A-1");
+        head.model = "LG TV".to_owned();
+        let text = strip_colors(&list_text([&redact_serial_number(&head)]));
+
+        assert!(text.contains("ID: lg-tv-hdmi-a-1"), "{text}");
+        assert!(!text.contains("redacted"), "{text}");
+    }
+}
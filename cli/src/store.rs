@@ -0,0 +1,56 @@
+// Copyright 2023 System76 <info@system76.com>
+// SPDX-License-Identifier: MPL-2.0
+
+//! Persistent state kept as JSON files under `$XDG_CONFIG_HOME/cosmic-randr`.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// Directory holding the persisted state of `cosmic-randr`.
+///
+/// # Errors
+///
+/// Returns error if neither `XDG_CONFIG_HOME` nor `HOME` is set.
+pub fn dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .ok_or("cannot locate config directory: neither XDG_CONFIG_HOME nor HOME is set")?;
+
+    Ok(base.join("cosmic-randr"))
+}
+
+/// Loads a persisted value, or its default if it was never saved.
+///
+/// # Errors
+///
+/// Returns error if the file exists but cannot be read or parsed.
+pub fn load<T: DeserializeOwned + Default>(file: &str) -> Result<T, Box<dyn std::error::Error>> {
+    let path = dir()?.join(file);
+
+    match std::fs::read(&path) {
+        Ok(data) => serde_json::from_slice(&data)
+            .map_err(|why| format!("failed to parse {}: {why}", path.display()).into()),
+        Err(why) if why.kind() == std::io::ErrorKind::NotFound => Ok(T::default()),
+        Err(why) => Err(format!("failed to read {}: {why}", path.display()).into()),
+    }
+}
+
+/// Persists a value, replacing the previous file atomically.
+///
+/// # Errors
+///
+/// Returns error if the config directory or file cannot be written.
+pub fn save<T: Serialize>(file: &str, value: &T) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = dir()?;
+    std::fs::create_dir_all(&dir)?;
+
+    let path = dir.join(file);
+    let temporary = path.with_extension("tmp");
+    std::fs::write(&temporary, serde_json::to_vec_pretty(value)?)?;
+    std::fs::rename(&temporary, &path)?;
+
+    Ok(())
+}
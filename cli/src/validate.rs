@@ -0,0 +1,91 @@
+// Copyright 2023 System76 <info@system76.com>
+// SPDX-License-Identifier: MPL-2.0
+
+//! Checks a KDL layout against the outputs that are currently connected.
+
+use cosmic_randr::output_head::OutputHead;
+use cosmic_randr::Context;
+use cosmic_randr_shell::List;
+use serde::Serialize;
+use std::ops::RangeInclusive;
+
+/// Scales accepted for an output in a layout.
+pub const SCALE_RANGE: RangeInclusive<f64> = 0.5..=4.0;
+
+/// A reason that the layout cannot be applied as written.
+#[derive(Debug, Serialize)]
+pub struct Problem {
+    /// Output of the layout that the problem was found in.
+    pub output: Option<String>,
+    pub message: String,
+}
+
+/// Every problem that prevents the layout from being applied to the connected outputs.
+pub fn check(context: &Context, list: &List) -> Vec<Problem> {
+    let mut problems = Vec::new();
+
+    for output in list.outputs.values() {
+        let mut problem = |message: String| {
+            problems.push(Problem {
+                output: Some(output.name.clone()),
+                message,
+            });
+        };
+
+        let Some(head) = find_head(context, &output.name, output.serial_number.as_deref()) else {
+            problem(String::from("output is not connected"));
+            continue;
+        };
+
+        if !output.enabled {
+            continue;
+        }
+
+        if let Some(mode) = list.current_mode(output) {
+            let (width, height) = (mode.size.0 as i32, mode.size.1 as i32);
+
+            if head
+                .find_mode(width, height, mode.refresh_rate as i32)
+                .is_none()
+            {
+                problem(format!(
+                    "{} has no {width}x{height} mode within 0.5 Hz of {:.3} Hz",
+                    head.name,
+                    mode.refresh_hz()
+                ));
+            }
+        }
+
+        if !SCALE_RANGE.contains(&output.scale) {
+            problem(format!(
+                "scale {} is outside of {} to {}",
+                output.scale,
+                SCALE_RANGE.start(),
+                SCALE_RANGE.end()
+            ));
+        }
+
+        if let Some(source) = output.mirroring.as_deref() {
+            if !context
+                .output_heads
+                .values()
+                .any(|head| head.name == source)
+            {
+                problem(format!("mirroring source {source} is not connected"));
+            }
+        }
+    }
+
+    problems
+}
+
+/// Finds the connected output for an output of the layout, preferring its serial number
+/// so that a layout still applies when a display moves to another connector.
+fn find_head<'a>(context: &'a Context, name: &str, serial: Option<&str>) -> Option<&'a OutputHead> {
+    let heads = || context.output_heads.values();
+
+    serial
+        .filter(|serial| !serial.is_empty())
+        .and_then(|serial| heads().find(|head| head.serial_number == serial))
+        .or_else(|| heads().find(|head| head.name == name))
+}
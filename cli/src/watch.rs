@@ -0,0 +1,195 @@
+// Copyright 2023 System76 <info@system76.com>
+// SPDX-License-Identifier: MPL-2.0
+
+//! Reporting of output changes with `watch`.
+
+use crate::list::list_kdl;
+use crate::{App, ModeSort, OutputSort};
+use cosmic_randr::output_head::OutputHead;
+use cosmic_randr::Context;
+use cosmic_randr_shell::Transform;
+use std::collections::BTreeMap;
+use std::fmt::Write as FmtWrite;
+use std::io::Write;
+
+/// State of an output that `watch` reports changes of.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct WatchedOutput {
+    /// Make, model and serial number, which some docks and KVMs report late.
+    identity: (String, String, String),
+    enabled: bool,
+    mirroring: Option<String>,
+    mode: Option<(i32, i32, i32)>,
+    position: (i32, i32),
+    scale: f64,
+    transform: Option<Transform>,
+}
+
+impl WatchedOutput {
+    pub fn new(head: &OutputHead) -> Self {
+        Self {
+            identity: (
+                head.make.clone(),
+                head.model.clone(),
+                head.serial_number.clone(),
+            ),
+            enabled: head.enabled,
+            mirroring: head.mirroring.clone(),
+            mode: head
+                .current_mode
+                .as_ref()
+                .and_then(|id| head.modes.get(id))
+                .map(|mode| (mode.width, mode.height, mode.refresh)),
+            position: (head.position_x, head.position_y),
+            scale: head.scale,
+            transform: head
+                .transform
+                .and_then(|wl_transform| Transform::try_from(wl_transform).ok()),
+        }
+    }
+
+    /// Describes what changed from `previous`, one line per setting.
+    pub fn changes(&self, name: &str, previous: &Self) -> Vec<String> {
+        let mut changes = Vec::new();
+
+        if self.identity != previous.identity {
+            let (make, model, serial) = &self.identity;
+            let mut change = format!("{name}: identified as {make} {model}");
+            if !serial.is_empty() {
+                let _res = write!(change, ", serial {serial}");
+            }
+            changes.push(change);
+        }
+
+        if self.enabled != previous.enabled {
+            let state = if self.enabled { "enabled" } else { "disabled" };
+            changes.push(format!("{state} {name}"));
+        }
+
+        if self.mode != previous.mode {
+            if let Some((width, height, refresh)) = self.mode {
+                changes.push(format!(
+                    "{name}: mode {width}x{height} @ {}.{:03} Hz",
+                    refresh / 1000,
+                    refresh % 1000
+                ));
+            }
+        }
+
+        if self.position != previous.position {
+            let (x, y) = self.position;
+            changes.push(format!("{name}: position {x},{y}"));
+        }
+
+        if self.scale != previous.scale {
+            changes.push(format!("{name}: scale {:.2}", self.scale));
+        }
+
+        if self.transform != previous.transform {
+            if let Some(transform) = self.transform {
+                changes.push(format!("{name}: transform {transform}"));
+            }
+        }
+
+        if self.mirroring != previous.mirroring {
+            changes.push(match &self.mirroring {
+                Some(from) => format!("{name}: mirroring {from}"),
+                None => format!("{name}: extended"),
+            });
+        }
+
+        changes
+    }
+}
+
+impl App {
+    /// Prints the changes to the outputs each time the compositor sends a new state.
+    pub async fn watch(
+        &mut self,
+        kdl: bool,
+        compact: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.dispatch_until_manager_done().await?;
+
+        let watched = |context: &Context| {
+            context
+                .output_heads
+                .values()
+                .map(|head| (head.name.clone(), WatchedOutput::new(head)))
+                .collect::<BTreeMap<_, _>>()
+        };
+
+        let mut outputs = watched(&self.context);
+
+        if kdl {
+            self.sort_modes(ModeSort::Res, false);
+            list_kdl(&self.context, OutputSort::Name, compact);
+        }
+
+        loop {
+            // Each `done` event concludes a new generation of output state, identified
+            // by the serial of the manager.
+            let serial = self.context.output_manager_serial;
+            self.wait_for_manager_done().await?;
+            if self.context.output_manager_serial == serial {
+                continue;
+            }
+
+            let previous = std::mem::replace(&mut outputs, watched(&self.context));
+
+            let mut changes = Vec::new();
+            for name in previous.keys().filter(|name| !outputs.contains_key(*name)) {
+                changes.push(format!("removed {name}"));
+            }
+
+            for (name, output) in &outputs {
+                match previous.get(name) {
+                    Some(previous) => changes.extend(output.changes(name, previous)),
+                    None => {
+                        changes.push(format!("added {name}"));
+                        changes.extend(output.changes(name, &WatchedOutput::default()));
+                    }
+                }
+            }
+
+            if changes.is_empty() {
+                continue;
+            }
+
+            if kdl {
+                self.sort_modes(ModeSort::Res, false);
+                list_kdl(&self.context, OutputSort::Name, compact);
+                println!();
+            } else {
+                let mut stdout = std::io::stdout().lock();
+                for change in changes {
+                    writeln!(stdout, "{change}")?;
+                }
+                stdout.flush()?;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn late_identification() {
+        let generic = WatchedOutput {
+            identity: ("Generic".into(), "Monitor".into(), String::new()),
+            ..WatchedOutput::default()
+        };
+        let identified = WatchedOutput {
+            identity: ("Dell Inc.".into(), "DELL U2723QE".into(), "ABC123".into()),
+            ..generic.clone()
+        };
+
+        assert_eq!(
+            identified.changes("DP-1", &generic),
+            ["DP-1: identified as Dell Inc. DELL U2723QE, serial ABC123"]
+        );
+        assert!(identified.changes("DP-1", &identified).is_empty());
+    }
+}
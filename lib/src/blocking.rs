@@ -0,0 +1,192 @@
+// Copyright 2023 System76 <info@system76.com>
+// SPDX-License-Identifier: MPL-2.0
+
+//! Synchronous access to the outputs, for programs without an async runtime.
+
+use crate::context::{Configuration, ConfigureHeadError, HeadConfiguration};
+use crate::output_head::OutputHead;
+use crate::{ConfigOutcome, Context, Error, Message};
+use tachyonix::Receiver;
+use wayland_client::EventQueue;
+
+/// Capacity of the message channel, which is drained after every dispatch.
+const MESSAGE_CAPACITY: usize = 16;
+
+/// A [`Context`] whose events are dispatched by blocking on the connection.
+///
+/// Messages are drained internally, so that the channel never fills up. Nothing here
+/// requires a tokio runtime.
+///
+/// ```no_run
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut context = cosmic_randr::BlockingContext::connect()?;
+///
+/// for head in context.list_outputs()? {
+///     println!("{} {}", head.name, head.enabled);
+/// }
+///
+/// context.disable("HDMI-A-1")?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct BlockingContext {
+    pub context: Context,
+    pub event_queue: EventQueue<Context>,
+    receiver: Receiver<Message>,
+}
+
+impl BlockingContext {
+    /// Connects to the compositor and waits for the initial state of the outputs.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if there are any wayland client connection errors.
+    pub fn connect() -> Result<Self, Error> {
+        let (sender, receiver) = tachyonix::channel(MESSAGE_CAPACITY);
+        let (context, event_queue) = Context::connect(sender)?;
+
+        let mut blocking = Self {
+            context,
+            event_queue,
+            receiver,
+        };
+
+        // Heads are announced once the output manager is bound, followed by `done`,
+        // which may already have arrived while connecting.
+        let mut done = blocking.drain_messages();
+        while !done && blocking.context.output_manager.is_some() {
+            done = blocking.dispatch()?;
+        }
+
+        Ok(blocking)
+    }
+
+    /// Blocks until events arrive and dispatches them, returning whether the output
+    /// manager sent `done` in the meantime.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if dispatching events fails.
+    pub fn dispatch(&mut self) -> Result<bool, Error> {
+        self.event_queue.blocking_dispatch(&mut self.context)?;
+        Ok(self.drain_messages())
+    }
+
+    /// Dispatches every event that the compositor sent so far.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if dispatching events fails.
+    pub fn roundtrip(&mut self) -> Result<(), Error> {
+        self.event_queue.roundtrip(&mut self.context)?;
+        self.drain_messages();
+        Ok(())
+    }
+
+    /// The current state of every output.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if dispatching events fails.
+    pub fn list_outputs(&mut self) -> Result<Vec<OutputHead>, Error> {
+        self.roundtrip()?;
+
+        let mut heads = self
+            .context
+            .output_heads
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+        heads.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+        Ok(heads)
+    }
+
+    /// Applies a configuration created by [`Context::create_output_config`], and waits
+    /// for the outcome.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the remaining outputs could not be configured, or if
+    /// dispatching events fails.
+    pub fn apply(&mut self, config: Configuration) -> Result<ConfigOutcome, ConfigureHeadError> {
+        self.context.config_outcome = None;
+        config.apply()?;
+        self.wait_for_config_result()
+    }
+
+    /// Tests a configuration without applying it, and waits for the outcome.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the remaining outputs could not be configured, or if
+    /// dispatching events fails.
+    pub fn test(&mut self, config: Configuration) -> Result<ConfigOutcome, ConfigureHeadError> {
+        self.context.config_outcome = None;
+        config.test()?;
+        self.wait_for_config_result()
+    }
+
+    /// Enables the output, changing only what `head_config` gives.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the configuration cannot be created, or if dispatching events
+    /// fails.
+    pub fn enable(
+        &mut self,
+        output: &str,
+        head_config: Option<HeadConfiguration>,
+    ) -> Result<ConfigOutcome, ConfigureHeadError> {
+        let mut config = self.context.create_output_config()?;
+        config.enable_head(output, head_config)?;
+        self.apply(config)
+    }
+
+    /// Disables the output.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the configuration cannot be created, or if dispatching events
+    /// fails.
+    pub fn disable(&mut self, output: &str) -> Result<ConfigOutcome, ConfigureHeadError> {
+        let mut config = self.context.create_output_config()?;
+        config.disable_head(output)?;
+        self.apply(config)
+    }
+
+    /// Makes the output mirror `from`.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the configuration cannot be created, as without the COSMIC
+    /// extension, or if dispatching events fails.
+    pub fn mirror(
+        &mut self,
+        output: &str,
+        from: &str,
+    ) -> Result<ConfigOutcome, ConfigureHeadError> {
+        let mut config = self.context.create_output_config()?;
+        config.mirror_head(output, from, None)?;
+        self.apply(config)
+    }
+
+    /// Empties the message channel, returning whether the output manager sent `done`.
+    fn drain_messages(&mut self) -> bool {
+        let mut done = false;
+        while let Ok(message) = self.receiver.try_recv() {
+            done |= matches!(message, Message::ManagerDone);
+        }
+        done
+    }
+
+    fn wait_for_config_result(&mut self) -> Result<ConfigOutcome, ConfigureHeadError> {
+        loop {
+            if let Some(outcome) = self.context.config_outcome.take() {
+                return Ok(outcome);
+            }
+
+            self.dispatch()?;
+        }
+    }
+}
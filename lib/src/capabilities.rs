@@ -0,0 +1,78 @@
+// Copyright 2023 System76 <info@system76.com>
+// SPDX-License-Identifier: MPL-2.0
+
+use cosmic_protocols::output_management::v1::client::{
+    zcosmic_output_configuration_head_v1, zcosmic_output_configuration_v1,
+};
+
+/// Features of the COSMIC output management extension that the compositor supports.
+///
+/// Features were added to the extension over several versions, so a compositor may
+/// support some of them but not others. Every object of the extension shares the
+/// version of the manager, from which this is computed once when it is bound.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct CosmicCapabilities {
+    /// Version of the extension that was bound, or 0 without the extension.
+    pub version: u32,
+    /// Scales are set in thousandths rather than rounded by wlroots.
+    pub fractional_scale: bool,
+    /// Outputs can mirror other outputs.
+    pub mirroring: bool,
+    /// Adaptive sync can be left to the compositor to manage.
+    pub automatic_adaptive_sync: bool,
+}
+
+impl CosmicCapabilities {
+    #[must_use]
+    pub fn new(version: u32) -> Self {
+        Self {
+            version,
+            fractional_scale: version
+                >= zcosmic_output_configuration_head_v1::REQ_SET_SCALE_1000_SINCE,
+            mirroring: version >= zcosmic_output_configuration_v1::REQ_MIRROR_HEAD_SINCE,
+            automatic_adaptive_sync: version
+                >= zcosmic_output_configuration_head_v1::REQ_SET_ADAPTIVE_SYNC_EXT_SINCE,
+        }
+    }
+
+    /// Explains which features are missing when the extension is only partially
+    /// supported, such as "your COSMIC version supports fractional scale and mirroring,
+    /// but not automatic adaptive sync".
+    #[must_use]
+    pub fn partial_support(&self) -> Option<String> {
+        if self.version == 0 {
+            return None;
+        }
+
+        let features = [
+            ("fractional scale", self.fractional_scale),
+            ("mirroring", self.mirroring),
+            ("automatic adaptive sync", self.automatic_adaptive_sync),
+        ];
+
+        let list = |supported: bool| {
+            features
+                .iter()
+                .filter(|(_, available)| *available == supported)
+                .map(|(feature, _)| *feature)
+                .collect::<Vec<_>>()
+                .join(" and ")
+        };
+
+        let (supported, unsupported) = (list(true), list(false));
+
+        if unsupported.is_empty() {
+            None
+        } else if supported.is_empty() {
+            Some(format!(
+                "your COSMIC version (extension version {}) does not support {unsupported}",
+                self.version
+            ))
+        } else {
+            Some(format!(
+                "your COSMIC version (extension version {}) supports {supported}, but not {unsupported}",
+                self.version
+            ))
+        }
+    }
+}
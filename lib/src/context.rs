@@ -2,22 +2,26 @@
 // SPDX-License-Identifier: MPL-2.0
 
 use crate::output_head::OutputHead;
-use crate::{Error, Message};
-use cosmic_protocols::output_management::v1::client::zcosmic_output_configuration_head_v1::{
-    self, ZcosmicOutputConfigurationHeadV1,
-};
+use crate::output_mode::{select_mode, ModeTiming};
+use crate::{ConfigOutcome, CosmicCapabilities, Error, Message};
+use cosmic_protocols::output_management::v1::client::zcosmic_output_configuration_head_v1::ZcosmicOutputConfigurationHeadV1;
 use cosmic_protocols::output_management::v1::client::zcosmic_output_configuration_v1::ZcosmicOutputConfigurationV1;
-use cosmic_protocols::output_management::v1::client::zcosmic_output_head_v1::AdaptiveSyncStateExt;
+use cosmic_protocols::output_management::v1::client::zcosmic_output_head_v1::{
+    AdaptiveSyncAvailability, AdaptiveSyncStateExt,
+};
 use cosmic_protocols::output_management::v1::client::zcosmic_output_manager_v1::ZcosmicOutputManagerV1;
-use std::collections::HashMap;
+use indexmap::IndexMap;
 use std::fmt;
+use std::time::Duration;
 use tachyonix::Sender;
 use wayland_client::protocol::{
     wl_callback::WlCallback, wl_output::Transform, wl_registry::WlRegistry,
 };
 use wayland_client::{backend::ObjectId, Connection, Proxy, QueueHandle};
 use wayland_client::{DispatchError, EventQueue};
-use wayland_protocols_wlr::output_management::v1::client::zwlr_output_configuration_head_v1::ZwlrOutputConfigurationHeadV1;
+use wayland_protocols_wlr::output_management::v1::client::zwlr_output_configuration_head_v1::{
+    self, ZwlrOutputConfigurationHeadV1,
+};
 use wayland_protocols_wlr::output_management::v1::client::zwlr_output_configuration_v1::ZwlrOutputConfigurationV1;
 use wayland_protocols_wlr::output_management::v1::client::zwlr_output_head_v1::{
     AdaptiveSyncState, ZwlrOutputHeadV1,
@@ -34,12 +38,20 @@ pub struct Context {
     pub cosmic_output_manager: Option<ZcosmicOutputManagerV1>,
     pub output_manager_serial: u32,
     pub output_manager_version: u32,
+    /// Features of the COSMIC extension that the compositor supports.
+    pub cosmic_capabilities: CosmicCapabilities,
 
-    pub output_heads: HashMap<ObjectId, OutputHead>,
+    /// Outputs in the order that the compositor advertised them.
+    pub output_heads: IndexMap<ObjectId, OutputHead>,
     pub wl_registry: WlRegistry,
 
     pub cosmic_manager_sync_callback: Option<WlCallback>,
     pub done_queued: bool,
+
+    /// Tests every configuration instead of applying it.
+    pub dry_run: bool,
+
+    pub(crate) config_outcome: Option<ConfigOutcome>,
 }
 
 #[derive(Debug)]
@@ -47,10 +59,13 @@ pub struct Configuration {
     obj: ZwlrOutputConfigurationV1,
     cosmic_obj: Option<ZcosmicOutputConfigurationV1>,
     cosmic_output_manager: Option<ZcosmicOutputManagerV1>,
+    cosmic_capabilities: CosmicCapabilities,
     handle: QueueHandle<Context>,
 
     known_heads: Vec<OutputHead>,
     configured_heads: Vec<String>,
+    dry_run: bool,
+    changes: Vec<String>,
 }
 
 #[derive(Debug, Default)]
@@ -61,12 +76,75 @@ pub struct HeadConfiguration {
     pub refresh: Option<f32>,
     /// Specifies the adaptive_sync mode to apply to the output.
     pub adaptive_sync: Option<AdaptiveSyncStateExt>,
+    /// Enable adaptive sync when it can't be left to the compositor, rather than fail
+    /// with [`ConfigurationError::UnsupportedVrrState`].
+    pub adaptive_sync_fallback: bool,
     /// Position the output within this x pixel coordinate.
     pub pos: Option<(i32, i32)>,
     /// Changes the dimensions of the output picture.
     pub scale: Option<f64>,
     /// Specifies a transformation matrix to apply to the output.
     pub transform: Option<Transform>,
+    /// What to do when no mode has the requested refresh rate.
+    pub on_missing_refresh: MissingRefresh,
+}
+
+impl fmt::Display for HeadConfiguration {
+    /// Lists the settings that are changed, such as `2560x1440 @ 143.999 Hz, scale 1.5`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut settings = Vec::new();
+
+        match (self.size, self.refresh) {
+            (Some((width, height)), Some(refresh)) => {
+                settings.push(format!("{width}x{height} @ {refresh:.3} Hz"));
+            }
+            (Some((width, height)), None) => settings.push(format!("{width}x{height}")),
+            (None, Some(refresh)) => settings.push(format!("{refresh:.3} Hz")),
+            (None, None) => (),
+        }
+
+        if let Some((x, y)) = self.pos {
+            settings.push(format!("position {x},{y}"));
+        }
+
+        if let Some(scale) = self.scale {
+            settings.push(format!("scale {scale}"));
+        }
+
+        if let Some(transform) = self
+            .transform
+            .and_then(|transform| cosmic_randr_shell::Transform::try_from(transform).ok())
+        {
+            settings.push(format!("transform {transform}"));
+        }
+
+        if let Some(sync) = self
+            .adaptive_sync
+            .and_then(|sync| cosmic_randr_shell::AdaptiveSyncState::try_from(sync).ok())
+        {
+            settings.push(format!("adaptive sync {sync}"));
+        }
+
+        if settings.is_empty() {
+            f.write_str("current settings")
+        } else {
+            f.write_str(&settings.join(", "))
+        }
+    }
+}
+
+/// Fallback for a refresh rate that no mode of the requested size offers.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum MissingRefresh {
+    /// Fail with [`ConfigurationError::ModeNotFound`].
+    #[default]
+    Error,
+    /// Use the closest refresh rate available at the size.
+    Nearest,
+    /// Use the refresh rate of the preferred mode.
+    Preferred,
+    /// Keep the current refresh rate, and only change the size.
+    Ignore,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -78,6 +156,9 @@ pub enum ConfigurationError {
     PositionForMirroredOutput,
     MirroringItself,
     UnsupportedVrrState,
+    ManagerGone,
+    NoModes,
+    NoCustomModes,
 }
 
 impl fmt::Display for ConfigurationError {
@@ -86,17 +167,51 @@ impl fmt::Display for ConfigurationError {
             Self::OutputAlreadyConfigured => f.write_str("Output configured twice"),
             Self::UnknownOutput => f.write_str("Unknown output"),
             Self::ModeNotFound => f.write_str("Unknown or unsupported mode"),
-            Self::NoCosmicExtension => f.write_str("Mirroring isn't available outside COSMIC"),
+            Self::NoCosmicExtension => {
+                f.write_str("Only available with the COSMIC output management extension")
+            }
             Self::PositionForMirroredOutput => f.write_str("You cannot position a mirrored output"),
             Self::MirroringItself => f.write_str("Output mirroring itself"),
             Self::UnsupportedVrrState => {
                 f.write_str("Automatic VRR state management isn't available outside COSMIC")
             }
+            Self::ManagerGone => f.write_str("The compositor stopped managing outputs"),
+            Self::NoModes => f.write_str("Output reports no modes yet; try again"),
+            Self::NoCustomModes => f.write_str("The compositor does not accept custom modes"),
         }
     }
 }
 impl std::error::Error for ConfigurationError {}
 
+/// Outputs that could not be configured to their current state when completing a configuration.
+#[derive(Debug, Clone)]
+pub struct UnconfiguredHeads {
+    /// Name of each output that failed, with the reason it failed.
+    pub failures: Vec<(String, ConfigurationError)>,
+}
+
+impl fmt::Display for UnconfiguredHeads {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("failed to configure outputs:")?;
+        for (output, why) in &self.failures {
+            write!(f, " {output} ({why})")?;
+        }
+        Ok(())
+    }
+}
+impl std::error::Error for UnconfiguredHeads {}
+
+/// Why [`Context::configure_head`] did not get an outcome from the compositor.
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigureHeadError {
+    #[error(transparent)]
+    Configuration(#[from] ConfigurationError),
+    #[error(transparent)]
+    UnconfiguredHeads(#[from] UnconfiguredHeads),
+    #[error(transparent)]
+    Dispatch(#[from] Error),
+}
+
 impl Configuration {
     pub fn disable_head(&mut self, output: &str) -> Result<(), ConfigurationError> {
         if self.configured_heads.iter().any(|o| o == output) {
@@ -111,6 +226,10 @@ impl Configuration {
             .ok_or(ConfigurationError::UnknownOutput)?;
         self.obj.disable_head(&head.wlr_head);
 
+        if self.dry_run {
+            self.changes.push(format!("{output}: disable"));
+        }
+
         Ok(())
     }
 
@@ -124,11 +243,15 @@ impl Configuration {
         }
         self.configured_heads.push(output.to_string());
 
-        let head = self
-            .known_heads
-            .iter()
-            .find(|head| head.name == output)
-            .ok_or(ConfigurationError::UnknownOutput)?;
+        let head = head_with_modes(&self.known_heads, output)?;
+
+        if self.dry_run {
+            self.changes.push(match mode.as_ref() {
+                Some(mode) => format!("{output}: enable with {mode}"),
+                None => format!("{output}: enable with current settings"),
+            });
+        }
+
         let head_config = self.obj.enable_head(&head.wlr_head, &self.handle, ());
         let cosmic_head_config = self
             .cosmic_output_manager
@@ -136,9 +259,61 @@ impl Configuration {
             .map(|extension| extension.get_configuration_head(&head_config, &self.handle, ()));
 
         if let Some(args) = mode {
-            send_mode_to_config_head(head, head_config, cosmic_head_config, args)?;
+            send_mode_to_config_head(
+                head,
+                head_config,
+                cosmic_head_config,
+                self.cosmic_capabilities,
+                args,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Enables the output at a mode that it does not advertise, such as a resolution
+    /// that the panel supports but that is missing from its EDID.
+    ///
+    /// The refresh rate is in millihertz, or 0 to let the compositor choose one. The
+    /// compositor may still reject the mode when the configuration is applied.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConfigurationError::NoCustomModes`] if the bound version of
+    /// wlr-output-management has no request for custom modes.
+    pub fn enable_head_custom_mode(
+        &mut self,
+        output: &str,
+        width: i32,
+        height: i32,
+        refresh: i32,
+    ) -> Result<(), ConfigurationError> {
+        if self.obj.version() < zwlr_output_configuration_head_v1::REQ_SET_CUSTOM_MODE_SINCE {
+            return Err(ConfigurationError::NoCustomModes);
+        }
+
+        if self.configured_heads.iter().any(|o| o == output) {
+            return Err(ConfigurationError::OutputAlreadyConfigured);
+        }
+        self.configured_heads.push(output.to_string());
+
+        let head = self
+            .known_heads
+            .iter()
+            .find(|head| head.name == output)
+            .ok_or(ConfigurationError::UnknownOutput)?;
+
+        if self.dry_run {
+            self.changes.push(format!(
+                "{output}: enable with custom mode {width}x{height} @ {}.{:03} Hz",
+                refresh / 1000,
+                refresh % 1000
+            ));
         }
 
+        let head_config = self.obj.enable_head(&head.wlr_head, &self.handle, ());
+        head_config.set_custom_mode(width, height, refresh);
+
         Ok(())
     }
 
@@ -148,9 +323,14 @@ impl Configuration {
         mirrored: &str,
         mode: Option<HeadConfiguration>,
     ) -> Result<(), ConfigurationError> {
-        if self.cosmic_obj.is_none() {
+        let Some(cosmic_obj) = self
+            .cosmic_obj
+            .as_ref()
+            .filter(|_| self.cosmic_capabilities.mirroring)
+            .cloned()
+        else {
             return Err(ConfigurationError::NoCosmicExtension);
-        }
+        };
 
         if self.configured_heads.iter().any(|o| o == output) {
             return Err(ConfigurationError::OutputAlreadyConfigured);
@@ -177,7 +357,13 @@ impl Configuration {
             .find(|head| head.name == mirrored)
             .ok_or(ConfigurationError::UnknownOutput)?;
 
-        let cosmic_obj = self.cosmic_obj.as_ref().unwrap();
+        if self.dry_run {
+            self.changes.push(match mode.as_ref() {
+                Some(mode) => format!("{output}: mirror {mirrored} with {mode}"),
+                None => format!("{output}: mirror {mirrored}"),
+            });
+        }
+
         let head_config =
             cosmic_obj.mirror_head(&head.wlr_head, &mirror_head.wlr_head, &self.handle, ());
         let cosmic_head_config = self
@@ -186,39 +372,97 @@ impl Configuration {
             .map(|extension| extension.get_configuration_head(&head_config, &self.handle, ()));
 
         if let Some(args) = mode {
-            send_mode_to_config_head(head, head_config, cosmic_head_config, args)?;
+            send_mode_to_config_head(
+                head,
+                head_config,
+                cosmic_head_config,
+                self.cosmic_capabilities,
+                args,
+            )?;
         }
 
         Ok(())
     }
 
-    fn configure_remaining_heads(&mut self) {
+    fn configure_remaining_heads(&mut self) -> Result<(), UnconfiguredHeads> {
         let known_heads = self.known_heads.clone();
         let configured_heads = self.configured_heads.clone();
+        let mut failures = Vec::new();
+
         for output in known_heads
             .iter()
             .filter(|output| !configured_heads.iter().any(|name| *name == output.name))
         {
-            if output.enabled {
+            let result = if output.enabled {
                 if let Some(from) = output.mirroring.as_ref() {
-                    self.mirror_head(&output.name, from, None).unwrap();
+                    self.mirror_head(&output.name, from, None)
                 } else {
-                    self.enable_head(&output.name, None).unwrap();
+                    self.enable_head(&output.name, None)
                 }
             } else {
-                self.disable_head(&output.name).unwrap();
+                self.disable_head(&output.name)
+            };
+
+            if let Err(why) = result {
+                failures.push((output.name.clone(), why));
             }
         }
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(UnconfiguredHeads { failures })
+        }
     }
 
-    pub fn test(mut self) {
-        self.configure_remaining_heads();
+    /// Tests the configuration without applying it.
+    ///
+    /// # Errors
+    ///
+    /// Returns every output that could not be kept at its current state, in which case
+    /// the configuration is cancelled rather than sent incomplete.
+    pub fn test(mut self) -> Result<(), UnconfiguredHeads> {
+        if let Err(why) = self.configure_remaining_heads() {
+            self.cancel();
+            return Err(why);
+        }
+
         self.obj.test();
+        Ok(())
+    }
+
+    /// Applies the configuration, or only tests it under [`Context::dry_run`].
+    ///
+    /// # Errors
+    ///
+    /// Returns every output that could not be kept at its current state, in which case
+    /// the configuration is cancelled rather than sent incomplete.
+    pub fn apply(mut self) -> Result<(), UnconfiguredHeads> {
+        if let Err(why) = self.configure_remaining_heads() {
+            self.cancel();
+            return Err(why);
+        }
+
+        if self.dry_run {
+            self.obj.test();
+        } else {
+            self.obj.apply();
+        }
+
+        Ok(())
+    }
+
+    /// Whether the configuration is only tested when it is applied.
+    #[must_use]
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
     }
 
-    pub fn apply(mut self) {
-        self.configure_remaining_heads();
-        self.obj.apply();
+    /// Describes each output configured so far under [`Context::dry_run`], such as
+    /// `DP-1: enable with 2560x1440 @ 143.999 Hz, position 0,0`.
+    #[must_use]
+    pub fn changes(&self) -> &[String] {
+        &self.changes
     }
 
     pub fn cancel(self) {
@@ -230,10 +474,14 @@ fn send_mode_to_config_head(
     head: &OutputHead,
     head_config: ZwlrOutputConfigurationHeadV1,
     cosmic_head_config: Option<ZcosmicOutputConfigurationHeadV1>,
+    cosmic_capabilities: CosmicCapabilities,
     args: HeadConfiguration,
 ) -> Result<(), ConfigurationError> {
     if let Some(scale) = args.scale {
-        if let Some(cosmic_obj) = cosmic_head_config.as_ref() {
+        if let Some(cosmic_obj) = cosmic_head_config
+            .as_ref()
+            .filter(|_| cosmic_capabilities.fractional_scale)
+        {
             cosmic_obj.set_scale_1000((scale * 1000.0) as i32);
         } else {
             head_config.set_scale(scale);
@@ -261,36 +509,41 @@ fn send_mode_to_config_head(
     };
 
     if let Some(vrr) = args.adaptive_sync {
-        if let Some(cosmic_obj) = cosmic_head_config.as_ref().filter(|obj| {
-            obj.version() >= zcosmic_output_configuration_head_v1::REQ_SET_ADAPTIVE_SYNC_EXT_SINCE
-        }) {
+        if let Some(cosmic_obj) = cosmic_head_config
+            .as_ref()
+            .filter(|_| cosmic_capabilities.automatic_adaptive_sync)
+        {
             cosmic_obj.set_adaptive_sync_ext(vrr);
         } else {
-            head_config.set_adaptive_sync(match vrr {
-                AdaptiveSyncStateExt::Always => AdaptiveSyncState::Enabled,
-                AdaptiveSyncStateExt::Disabled => AdaptiveSyncState::Disabled,
-                AdaptiveSyncStateExt::Automatic => {
-                    return Err(ConfigurationError::UnsupportedVrrState)
-                }
-                _ => panic!("Unknown AdaptiveSyncStatExt variant"),
-            });
+            head_config.set_adaptive_sync(wlr_adaptive_sync(vrr, args.adaptive_sync_fallback)?);
         }
     }
 
     if let Some(refresh) = args.refresh {
+        // Rounded, as truncating would turn 143.999 into 143998 mHz.
         #[allow(clippy::cast_possible_truncation)]
-        let refresh = (refresh * 1000.0) as i32;
+        let refresh = (refresh * 1000.0).round() as i32;
 
-        let min = refresh - 501;
-        let max = refresh + 501;
+        let (size, on_missing_refresh) = refresh_target(
+            args.size,
+            head.current(),
+            head.preferred(),
+            args.on_missing_refresh,
+        );
 
-        let mode = mode_iter()
-            .find(|mode| mode.refresh == refresh)
-            .or_else(|| {
-                mode_iter()
-                    .filter(|mode| min < mode.refresh && max > mode.refresh)
-                    .min_by_key(|mode| (mode.refresh - refresh).abs())
-            });
+        let mode = size.and_then(|size| {
+            select_mode_or_fallback(
+                head.modes.values(),
+                size,
+                refresh,
+                on_missing_refresh,
+                head.modes
+                    .values()
+                    .find(|mode| mode.preferred)
+                    .map(|mode| mode.refresh),
+                head.current().map(|mode| mode.refresh),
+            )
+        });
 
         if let Some(mode) = mode {
             head_config.set_mode(&mode.wlr_mode);
@@ -308,6 +561,92 @@ fn send_mode_to_config_head(
     }
 }
 
+/// Finds the output among `heads`, which must have advertised its modes to be enabled.
+///
+/// Modes are advertised shortly after an output is connected, so a new output may
+/// have none yet.
+fn head_with_modes<'a>(
+    heads: &'a [OutputHead],
+    output: &str,
+) -> Result<&'a OutputHead, ConfigurationError> {
+    let head = heads
+        .iter()
+        .find(|head| head.name == output)
+        .ok_or(ConfigurationError::UnknownOutput)?;
+
+    if head.modes.is_empty() {
+        Err(ConfigurationError::NoModes)
+    } else {
+        Ok(head)
+    }
+}
+
+/// The adaptive sync state to request through wlr-output-management.
+///
+/// It has no automatic state, so automatic adaptive sync is an error unless `fallback`
+/// is set, in which case it becomes always enabled, the closest state it offers.
+fn wlr_adaptive_sync(
+    requested: AdaptiveSyncStateExt,
+    fallback: bool,
+) -> Result<AdaptiveSyncState, ConfigurationError> {
+    match requested {
+        AdaptiveSyncStateExt::Always => Ok(AdaptiveSyncState::Enabled),
+        AdaptiveSyncStateExt::Disabled => Ok(AdaptiveSyncState::Disabled),
+        AdaptiveSyncStateExt::Automatic if fallback => Ok(AdaptiveSyncState::Enabled),
+        AdaptiveSyncStateExt::Automatic => Err(ConfigurationError::UnsupportedVrrState),
+        _ => Err(ConfigurationError::UnsupportedVrrState),
+    }
+}
+
+/// The size at which a refresh rate is requested, and the policy for a refresh rate
+/// that no mode of that size offers.
+///
+/// Without a size, the refresh rate is changed at the current resolution. An output
+/// without a current mode, such as one being enabled, uses its preferred resolution
+/// at the nearest refresh rate instead, unless another policy was chosen.
+fn refresh_target<M: ModeTiming>(
+    size: Option<(u32, u32)>,
+    current: Option<&M>,
+    preferred: Option<&M>,
+    policy: MissingRefresh,
+) -> (Option<(i32, i32)>, MissingRefresh) {
+    let inferred = size.is_none() && current.is_none();
+    let size = size
+        .map(|(width, height)| (width as i32, height as i32))
+        .or_else(|| current.or(preferred).map(ModeTiming::size));
+
+    let policy = match policy {
+        MissingRefresh::Error if inferred => MissingRefresh::Nearest,
+        policy => policy,
+    };
+
+    (size, policy)
+}
+
+/// Selects the mode of `size` nearest to `refresh`, in mHz, or else the mode that the
+/// policy falls back to.
+///
+/// `preferred` and `current` are the refresh rates of the output's preferred and current
+/// modes, which [`MissingRefresh::Preferred`] and [`MissingRefresh::Ignore`] fall back to.
+fn select_mode_or_fallback<'a, M: ModeTiming>(
+    modes: impl IntoIterator<Item = &'a M> + Clone,
+    size: (i32, i32),
+    refresh: i32,
+    policy: MissingRefresh,
+    preferred: Option<i32>,
+    current: Option<i32>,
+) -> Option<&'a M> {
+    select_mode(modes.clone(), size, refresh).or_else(|| match policy {
+        MissingRefresh::Error => None,
+        MissingRefresh::Nearest => modes
+            .into_iter()
+            .filter(|mode| mode.size() == size)
+            .min_by_key(|mode| (mode.refresh() - refresh).abs()),
+        MissingRefresh::Preferred => select_mode(modes, size, preferred?),
+        MissingRefresh::Ignore => select_mode(modes, size, current?),
+    })
+}
+
 impl Context {
     pub fn callback(
         &mut self,
@@ -322,30 +661,250 @@ impl Context {
             .map_err(Error::from)
     }
 
+    /// Dispatches events until the compositor has advertised at least `min_outputs`
+    /// outputs and concluded them with a `done` event, or until `timeout` elapses.
+    ///
+    /// Right after connecting, or while a display is being connected, the outputs may be
+    /// advertised over several `done` events, so the wait continues across them until
+    /// enough outputs are seen. Returns whether they were seen in time. Every `done` is
+    /// also sent as a [`Message::ManagerDone`], so the receiver must still be drained.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if dispatching the events fails.
+    pub async fn wait_for_outputs(
+        &mut self,
+        event_queue: &mut EventQueue<Self>,
+        min_outputs: usize,
+        timeout: Duration,
+    ) -> Result<bool, Error> {
+        let wait = async {
+            // The output state is complete once a `done` was received, and the COSMIC
+            // extension has sent its events for every new head.
+            while self.output_manager_serial == 0
+                || self.cosmic_manager_sync_callback.is_some()
+                || self.output_heads.len() < min_outputs
+            {
+                self.dispatch(event_queue).await?;
+            }
+
+            Ok(())
+        };
+
+        match tokio::time::timeout(timeout, wait).await {
+            Ok(result) => result.map(|()| true),
+            Err(_) => Ok(false),
+        }
+    }
+
+    /// Dispatches events until the compositor responds to the last configuration that
+    /// was applied or tested.
+    ///
+    /// Call this right after applying, before events are dispatched elsewhere, as any
+    /// outcome received before the call is discarded. The outcome is also sent as a
+    /// [`Message`], so the receiver must still be drained.
+    ///
+    /// ```no_run
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// use cosmic_randr::ConfigOutcome;
+    ///
+    /// let (sender, _receiver) = tachyonix::channel(5);
+    /// let (mut context, mut event_queue) = cosmic_randr::connect(sender)?;
+    ///
+    /// loop {
+    ///     let mut config = context.create_output_config()?;
+    ///     config.enable_head("DP-1", None)?;
+    ///     config.apply()?;
+    ///
+    ///     match context.wait_for_config_result(&mut event_queue).await? {
+    ///         ConfigOutcome::Succeeded => break,
+    ///         // The outputs changed in the meantime, so try again with their new state.
+    ///         ConfigOutcome::Cancelled => continue,
+    ///         ConfigOutcome::Failed => return Err("configuration failed".into()),
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns error if dispatching events fails.
+    pub async fn wait_for_config_result(
+        &mut self,
+        event_queue: &mut EventQueue<Self>,
+    ) -> Result<ConfigOutcome, Error> {
+        self.config_outcome = None;
+
+        loop {
+            if let Some(outcome) = self.config_outcome.take() {
+                return Ok(outcome);
+            }
+
+            self.dispatch(event_queue).await?;
+        }
+    }
+
+    /// Configures a single output, and waits for the compositor to apply or test it.
+    ///
+    /// Every other output is kept at its current state, as a configuration must describe
+    /// all outputs. An output that is mirroring another keeps mirroring it, unless
+    /// `head_config` gives it a position. When the outputs change before the
+    /// configuration is processed, [`ConfigOutcome::Cancelled`] is returned, and the
+    /// call may simply be repeated.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the configuration cannot be created for the output or for any of
+    /// the remaining outputs, or if dispatching events fails.
+    pub async fn configure_head(
+        &mut self,
+        event_queue: &mut EventQueue<Self>,
+        output: &str,
+        head_config: HeadConfiguration,
+        test: bool,
+    ) -> Result<ConfigOutcome, ConfigureHeadError> {
+        let mirroring = self
+            .output_heads
+            .values()
+            .find(|head| head.name == output)
+            .ok_or(ConfigurationError::UnknownOutput)?
+            .mirroring
+            .clone();
+
+        let mut config = self.create_output_config()?;
+
+        if let Some(from) = mirroring.filter(|_| head_config.pos.is_none()) {
+            config.mirror_head(output, &from, Some(head_config))?;
+        } else {
+            config.enable_head(output, Some(head_config))?;
+        }
+
+        if test {
+            config.test()?;
+        } else {
+            config.apply()?;
+        }
+
+        Ok(self.wait_for_config_result(event_queue).await?)
+    }
+
+    /// The current state of the outputs, as the list of `cosmic-randr-shell`.
+    ///
+    /// Outputs are ordered by name, and their modes in the order they were advertised.
+    #[must_use]
+    pub fn current_config(&self) -> cosmic_randr_shell::List {
+        let mut list = cosmic_randr_shell::List::default();
+
+        let mut heads = self.output_heads.values().collect::<Vec<_>>();
+        heads.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+
+        for head in heads {
+            let mut output = cosmic_randr_shell::Output::new();
+            output.name = head.name.clone();
+            output.id = Some(head.stable_id());
+            output.enabled = head.enabled;
+            output.mirroring = head.mirroring.clone();
+            output.make = (!head.make.is_empty()).then(|| head.make.clone());
+            output.model = head.model.clone();
+            output.serial_number =
+                (!head.serial_number.is_empty()).then(|| head.serial_number.clone());
+            output.physical = (head.physical_width as u32, head.physical_height as u32);
+            output.position = (head.position_x, head.position_y);
+            output.scale = head.scale;
+            output.transform = head.transform.and_then(|wl_transform| {
+                cosmic_randr_shell::Transform::try_from(wl_transform).ok()
+            });
+            output.adaptive_sync = head
+                .adaptive_sync
+                .and_then(|sync| cosmic_randr_shell::AdaptiveSyncState::try_from(sync).ok());
+            output.adaptive_sync_availability = head
+                .adaptive_sync_support
+                .map(cosmic_randr_shell::AdaptiveSyncAvailability::from);
+            output.vrr_range = head.vrr_range().map(|(min, max)| (min as u32, max as u32));
+            output.capabilities = Some(self.capabilities(head));
+
+            for mode in head.modes.values() {
+                let mode_key = list.modes.insert(mode.into());
+
+                if head.current_mode.as_ref() == Some(&mode.wlr_mode.id()) {
+                    output.current = Some(mode_key);
+                }
+
+                output.modes.push(mode_key);
+            }
+
+            list.outputs.insert(output);
+        }
+
+        list
+    }
+
+    /// Features of the output that can be configured, given its state and the versions
+    /// of the output management protocols that the compositor supports.
+    #[must_use]
+    pub fn capabilities(&self, head: &OutputHead) -> cosmic_randr_shell::Capabilities {
+        let cosmic = self.cosmic_capabilities;
+        let wlr_version = self
+            .output_manager
+            .as_ref()
+            .map_or(0, |manager| manager.version());
+
+        // The COSMIC extension reports availability, while wlroots only reports the state.
+        let adaptive_sync = match head.adaptive_sync_support {
+            Some(available) => matches!(
+                available,
+                AdaptiveSyncAvailability::Supported | AdaptiveSyncAvailability::RequiresModeset
+            ),
+            None => {
+                wlr_version >= zwlr_output_configuration_head_v1::REQ_SET_ADAPTIVE_SYNC_SINCE
+                    && head.adaptive_sync.is_some()
+            }
+        };
+
+        cosmic_randr_shell::Capabilities {
+            adaptive_sync,
+            automatic_adaptive_sync: adaptive_sync && cosmic.automatic_adaptive_sync,
+            fractional_scale: cosmic.fractional_scale,
+            mirroring: cosmic.mirroring,
+            transform: wlr_version > 0,
+            custom_modes: wlr_version > 0,
+        }
+    }
+
     pub async fn send(&mut self, event: Message) -> Result<(), tachyonix::SendError<Message>> {
         self.sender.send(event).await
     }
 
-    pub fn create_output_config(&mut self) -> Configuration {
-        let configuration = self.output_manager.as_ref().unwrap().create_configuration(
-            self.output_manager_serial,
-            &self.handle,
-            (),
-        );
+    /// Begins a new output configuration from the current state of the output heads.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the compositor has finished the output manager, as it does
+    /// when it is reloading or shutting down.
+    pub fn create_output_config(&mut self) -> Result<Configuration, ConfigurationError> {
+        let configuration = self
+            .output_manager
+            .as_ref()
+            .ok_or(ConfigurationError::ManagerGone)?
+            .create_configuration(self.output_manager_serial, &self.handle, ());
 
         let cosmic_configuration = self
             .cosmic_output_manager
             .as_ref()
             .map(|extension| extension.get_configuration(&configuration, &self.handle, ()));
 
-        Configuration {
+        Ok(Configuration {
             obj: configuration,
             cosmic_obj: cosmic_configuration,
             cosmic_output_manager: self.cosmic_output_manager.clone(),
+            cosmic_capabilities: self.cosmic_capabilities,
             handle: self.handle.clone(),
             known_heads: self.output_heads.values().cloned().collect(),
             configured_heads: Vec::new(),
-        }
+            dry_run: self.dry_run,
+            changes: Vec::new(),
+        })
     }
 
     pub fn connect(sender: Sender<Message>) -> Result<(Self, EventQueue<Self>), Error> {
@@ -364,11 +923,14 @@ impl Context {
             output_manager: Default::default(),
             cosmic_output_manager: Default::default(),
             output_manager_version: Default::default(),
+            cosmic_capabilities: CosmicCapabilities::default(),
             output_heads: Default::default(),
             sender,
             wl_registry,
             cosmic_manager_sync_callback: None,
             done_queued: false,
+            dry_run: false,
+            config_outcome: None,
         };
 
         event_queue.roundtrip(&mut context)?;
@@ -402,3 +964,270 @@ impl Context {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Modes of a 1440p panel, whose preferred mode is the first.
+    const MODES: [(i32, i32, i32); 3] = [
+        (2560, 1440, 59_951),
+        (2560, 1440, 143_999),
+        (1920, 1080, 60_000),
+    ];
+
+    fn select(refresh: i32, policy: MissingRefresh) -> Option<i32> {
+        select_mode_or_fallback(
+            &MODES,
+            (2560, 1440),
+            refresh,
+            policy,
+            Some(59_951),
+            Some(143_999),
+        )
+        .map(|mode| mode.2)
+    }
+
+    #[test]
+    fn available_refresh_ignores_policy() {
+        for policy in [
+            MissingRefresh::Error,
+            MissingRefresh::Nearest,
+            MissingRefresh::Preferred,
+            MissingRefresh::Ignore,
+        ] {
+            assert_eq!(select(144_000, policy), Some(143_999));
+        }
+    }
+
+    #[test]
+    fn missing_refresh_error() {
+        assert_eq!(select(120_000, MissingRefresh::Error), None);
+    }
+
+    #[test]
+    fn missing_refresh_nearest() {
+        assert_eq!(select(120_000, MissingRefresh::Nearest), Some(143_999));
+        assert_eq!(select(75_000, MissingRefresh::Nearest), Some(59_951));
+    }
+
+    #[test]
+    fn missing_refresh_preferred() {
+        assert_eq!(select(120_000, MissingRefresh::Preferred), Some(59_951));
+    }
+
+    #[test]
+    fn missing_refresh_ignore() {
+        assert_eq!(select(120_000, MissingRefresh::Ignore), Some(143_999));
+    }
+
+    #[test]
+    fn fallback_keeps_the_size() {
+        // The refresh rate of the preferred and current modes is not offered at 1080p.
+        let select = |policy| {
+            select_mode_or_fallback(
+                &MODES,
+                (1920, 1080),
+                144_000,
+                policy,
+                Some(143_999),
+                Some(143_999),
+            )
+        };
+
+        assert_eq!(select(MissingRefresh::Nearest), Some(&(1920, 1080, 60_000)));
+        assert_eq!(select(MissingRefresh::Preferred), None);
+        assert_eq!(select(MissingRefresh::Ignore), None);
+    }
+
+    #[test]
+    fn refresh_only_without_current_mode() {
+        let (size, policy) = refresh_target(None, None, Some(&MODES[0]), MissingRefresh::Error);
+        assert_eq!(size, Some((2560, 1440)));
+        assert_eq!(policy, MissingRefresh::Nearest);
+
+        // The preferred resolution, at the rate nearest to the one requested.
+        let mode = select_mode_or_fallback(&MODES, size.unwrap(), 130_000, policy, None, None);
+        assert_eq!(mode, Some(&(2560, 1440, 143_999)));
+    }
+
+    #[test]
+    fn refresh_policy_kept_unless_inferred() {
+        let current = Some(&MODES[2]);
+        let preferred = Some(&MODES[0]);
+
+        // The current resolution, or the one requested, keeps the policy.
+        assert_eq!(
+            refresh_target(None, current, preferred, MissingRefresh::Error),
+            (Some((1920, 1080)), MissingRefresh::Error)
+        );
+        assert_eq!(
+            refresh_target(Some((2560, 1440)), None, preferred, MissingRefresh::Error),
+            (Some((2560, 1440)), MissingRefresh::Error)
+        );
+        // A policy chosen explicitly is kept even for an inferred resolution.
+        assert_eq!(
+            refresh_target(None, None, preferred, MissingRefresh::Preferred),
+            (Some((2560, 1440)), MissingRefresh::Preferred)
+        );
+        assert_eq!(
+            refresh_target::<(i32, i32, i32)>(None, None, None, MissingRefresh::Error),
+            (None, MissingRefresh::Nearest)
+        );
+    }
+
+    #[test]
+    fn automatic_adaptive_sync_strict() {
+        assert!(matches!(
+            wlr_adaptive_sync(AdaptiveSyncStateExt::Automatic, false),
+            Err(ConfigurationError::UnsupportedVrrState)
+        ));
+    }
+
+    #[test]
+    fn automatic_adaptive_sync_fallback() {
+        assert_eq!(
+            wlr_adaptive_sync(AdaptiveSyncStateExt::Automatic, true).unwrap(),
+            AdaptiveSyncState::Enabled
+        );
+    }
+
+    #[test]
+    fn every_adaptive_sync_state() {
+        // Unknown states can't be constructed, but one added to the protocol must be
+        // given a wlroots state here rather than reported as unsupported.
+        for state in (0..8).filter_map(|value| AdaptiveSyncStateExt::try_from(value).ok()) {
+            assert!(wlr_adaptive_sync(state, true).is_ok(), "{state:?}");
+        }
+    }
+
+    #[test]
+    fn adaptive_sync_without_fallback() {
+        for fallback in [false, true] {
+            assert_eq!(
+                wlr_adaptive_sync(AdaptiveSyncStateExt::Always, fallback).unwrap(),
+                AdaptiveSyncState::Enabled
+            );
+            assert_eq!(
+                wlr_adaptive_sync(AdaptiveSyncStateExt::Disabled, fallback).unwrap(),
+                AdaptiveSyncState::Disabled
+            );
+        }
+    }
+
+    #[test]
+    fn head_without_modes() {
+        let heads = [OutputHead::inert("DP-1")];
+
+        assert!(matches!(
+            head_with_modes(&heads, "DP-1"),
+            Err(ConfigurationError::NoModes)
+        ));
+        assert!(matches!(
+            head_with_modes(&heads, "DP-2"),
+            Err(ConfigurationError::UnknownOutput)
+        ));
+    }
+
+    #[test]
+    fn head_with_a_mode() {
+        let mut head = OutputHead::inert("DP-1");
+        let mode = crate::OutputMode::new(Proxy::inert(head.wlr_head.backend().clone()));
+        head.modes.insert(mode.wlr_mode.id(), mode);
+        let heads = [head];
+
+        assert!(head_with_modes(&heads, "DP-1").is_ok());
+    }
+
+    #[test]
+    fn failing_remaining_head() {
+        let connection = crate::unbound_connection();
+        let event_queue = connection.new_event_queue::<Context>();
+
+        // The enabled output has no modes to keep, while the disabled one needs none.
+        let mut enabled = OutputHead::inert("DP-1");
+        enabled.enabled = true;
+        let config = Configuration {
+            obj: Proxy::inert(connection.backend().downgrade()),
+            cosmic_obj: None,
+            cosmic_output_manager: None,
+            cosmic_capabilities: CosmicCapabilities::default(),
+            handle: event_queue.handle(),
+            known_heads: vec![enabled, OutputHead::inert("DP-2")],
+            configured_heads: Vec::new(),
+            dry_run: false,
+            changes: Vec::new(),
+        };
+
+        let why = config.test().unwrap_err();
+        assert!(matches!(
+            why.failures.as_slice(),
+            [(output, ConfigurationError::NoModes)] if output == "DP-1"
+        ));
+    }
+
+    /// A context of the given outputs, without an output manager, which is not
+    /// connected to any compositor.
+    fn context(heads: Vec<OutputHead>) -> (Context, EventQueue<Context>) {
+        let connection = crate::unbound_connection();
+        let event_queue = connection.new_event_queue();
+
+        let context = Context {
+            handle: event_queue.handle(),
+            sender: tachyonix::channel(5).0,
+            output_manager: None,
+            cosmic_output_manager: None,
+            output_manager_serial: 0,
+            output_manager_version: 0,
+            cosmic_capabilities: CosmicCapabilities::default(),
+            output_heads: heads
+                .into_iter()
+                .map(|head| (head.wlr_head.id(), head))
+                .collect(),
+            wl_registry: Proxy::inert(connection.backend().downgrade()),
+            cosmic_manager_sync_callback: None,
+            done_queued: false,
+            dry_run: false,
+            config_outcome: None,
+            connection,
+        };
+
+        (context, event_queue)
+    }
+
+    #[test]
+    fn configure_unknown_head() {
+        let (mut context, mut event_queue) = context(vec![OutputHead::inert("DP-1")]);
+        let result = futures_lite::future::block_on(context.configure_head(
+            &mut event_queue,
+            "DP-2",
+            HeadConfiguration::default(),
+            true,
+        ));
+
+        assert!(matches!(
+            result,
+            Err(ConfigureHeadError::Configuration(
+                ConfigurationError::UnknownOutput
+            ))
+        ));
+    }
+
+    #[test]
+    fn configure_head_without_manager() {
+        let (mut context, mut event_queue) = context(vec![OutputHead::inert("DP-1")]);
+        let result = futures_lite::future::block_on(context.configure_head(
+            &mut event_queue,
+            "DP-1",
+            HeadConfiguration::default(),
+            true,
+        ));
+
+        assert!(matches!(
+            result,
+            Err(ConfigureHeadError::Configuration(
+                ConfigurationError::ManagerGone
+            ))
+        ));
+    }
+}
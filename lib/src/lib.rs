@@ -1,6 +1,12 @@
 // Copyright 2023 System76 <info@system76.com>
 // SPDX-License-Identifier: MPL-2.0
 
+pub mod blocking;
+pub use blocking::BlockingContext;
+
+pub mod capabilities;
+pub use capabilities::CosmicCapabilities;
+
 pub mod context;
 pub use context::Context;
 
@@ -12,6 +18,9 @@ pub mod output_manager;
 pub mod output_mode;
 pub use output_mode::OutputMode;
 
+pub mod reconnect;
+pub use reconnect::ReconnectingContext;
+
 pub use cosmic_protocols::output_management::v1::client::zcosmic_output_head_v1::{
     AdaptiveSyncAvailability, AdaptiveSyncStateExt,
 };
@@ -31,6 +40,22 @@ pub fn connect(sender: Sender<Message>) -> Result<(Context, EventQueue<Context>)
     Context::connect(sender)
 }
 
+/// A connection over a socket that no compositor listens on, so that its objects are inert.
+#[cfg(any(test, feature = "test-util"))]
+pub(crate) fn unbound_connection() -> Connection {
+    let (socket, _compositor) = std::os::unix::net::UnixStream::pair().unwrap();
+    Connection::from_socket(socket).unwrap()
+}
+
+/// Environment variable which, when set to anything other than `0`, makes [`Context`]
+/// ignore the COSMIC output management extension and behave as on any wlroots
+/// compositor. This is a debugging aid for the fallback code paths.
+pub const NO_COSMIC_ENV: &str = "COSMIC_RANDR_NO_COSMIC";
+
+fn cosmic_disabled() -> bool {
+    std::env::var_os(NO_COSMIC_ENV).is_some_and(|value| !value.is_empty() && value != "0")
+}
+
 #[allow(clippy::enum_variant_names)]
 #[derive(Clone, Copy, Debug)]
 pub enum Message {
@@ -39,9 +64,23 @@ pub enum Message {
     ConfigurationSucceeded,
     ManagerDone,
     ManagerFinished,
+    /// The connection was re-established after the compositor went away.
+    Reconnected,
     Unsupported,
 }
 
+/// Outcome of an output configuration that was applied or tested.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ConfigOutcome {
+    /// The configuration is now in effect, or would be if it was only tested.
+    Succeeded,
+    /// The compositor rejected the configuration.
+    Failed,
+    /// The outputs changed before the configuration was processed, so it should be
+    /// recreated from the new state and tried again.
+    Cancelled,
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("I/O error")]
@@ -1,7 +1,7 @@
 // Copyright 2023 System76 <info@system76.com>
 // SPDX-License-Identifier: MPL-2.0
 
-use super::{Context, Message};
+use super::{ConfigOutcome, Context, Message};
 use cosmic_protocols::output_management::v1::client::zcosmic_output_configuration_v1::ZcosmicOutputConfigurationV1;
 use wayland_client::{Connection, Dispatch, Proxy, QueueHandle};
 use wayland_protocols_wlr::output_management::v1::client::zwlr_output_configuration_v1::Event;
@@ -19,14 +19,17 @@ impl Dispatch<ZwlrOutputConfigurationV1, ()> for Context {
         futures_lite::future::block_on(async {
             match event {
                 Event::Succeeded => {
+                    state.config_outcome = Some(ConfigOutcome::Succeeded);
                     let _res = state.send(Message::ConfigurationSucceeded).await;
                     proxy.destroy();
                 }
                 Event::Failed => {
+                    state.config_outcome = Some(ConfigOutcome::Failed);
                     let _res = state.send(Message::ConfigurationFailed).await;
                     proxy.destroy();
                 }
                 Event::Cancelled => {
+                    state.config_outcome = Some(ConfigOutcome::Cancelled);
                     let _res = state.send(Message::ConfigurationCancelled).await;
                     proxy.destroy();
                 }
@@ -3,6 +3,7 @@
 
 use std::sync::Mutex;
 
+use crate::output_mode::select_mode;
 use crate::{Context, OutputMode};
 
 use cosmic_protocols::output_management::v1::client::zcosmic_output_head_v1::AdaptiveSyncAvailability;
@@ -25,6 +26,11 @@ use wayland_protocols_wlr::output_management::v1::client::zwlr_output_mode_v1::Z
 pub struct OutputHead {
     pub adaptive_sync: Option<AdaptiveSyncStateExt>,
     pub adaptive_sync_support: Option<AdaptiveSyncAvailability>,
+    /// The mode of the last configuration the compositor committed.
+    ///
+    /// Neither protocol announces a pending mode, so during a modeset this remains the
+    /// previous mode until the compositor reports the new one. Each report replaces the
+    /// last, so at most one mode is ever current.
     pub current_mode: Option<ObjectId>,
     pub description: String,
     pub enabled: bool,
@@ -103,7 +109,7 @@ impl Dispatch<ZwlrOutputHeadV1, ()> for Context {
                 if proxy.version() >= 3 {
                     proxy.release();
                 }
-                state.output_heads.remove(&proxy.id());
+                state.output_heads.shift_remove(&proxy.id());
             }
 
             ZwlrOutputHeadEvent::Make { make } => {
@@ -195,4 +201,112 @@ impl OutputHead {
             wlr_head,
         }
     }
+
+    /// An output without modes, whose protocol object is not bound to any compositor.
+    #[cfg(any(test, feature = "test-util"))]
+    #[must_use]
+    pub fn inert(name: &str) -> Self {
+        let connection = crate::unbound_connection();
+        let mut head = Self::new(Proxy::inert(connection.backend().downgrade()));
+        head.name = name.to_owned();
+        head
+    }
+
+    /// The mode that the output is currently driven at.
+    #[must_use]
+    pub fn current(&self) -> Option<&OutputMode> {
+        self.current_mode.as_ref().and_then(|id| self.modes.get(id))
+    }
+
+    /// The mode advertised as preferred, or else the largest mode of the output.
+    #[must_use]
+    pub fn preferred(&self) -> Option<&OutputMode> {
+        self.modes
+            .values()
+            .find(|mode| mode.preferred)
+            .or_else(|| self.modes.values().min())
+    }
+
+    /// The mode of the given size with the refresh rate nearest to `refresh`, in mHz.
+    ///
+    /// Compositors round refresh rates differently, so any mode within half a hertz of
+    /// the requested rate is accepted. See [`select_mode`].
+    #[must_use]
+    pub fn find_mode(&self, width: i32, height: i32, refresh: i32) -> Option<&OutputMode> {
+        select_mode(self.modes.values(), (width, height), refresh)
+    }
+
+    /// An identifier of the display that is as stable as its EDID allows.
+    ///
+    /// This is the make, model and serial number of the display, in lowercase with every
+    /// run of other characters than letters and digits replaced by `-`, such as
+    /// `dell-u2720q-abc123`. Displays without a serial number use the connector name in
+    /// its place, so their identifier changes with the port they are plugged into.
+    #[must_use]
+    pub fn stable_id(&self) -> String {
+        let last = if self.serial_number.is_empty() {
+            &self.name
+        } else {
+            &self.serial_number
+        };
+
+        let mut id = String::new();
+        for part in [&self.make, &self.model, last] {
+            for word in part
+                .split(|c: char| !c.is_alphanumeric())
+                .filter(|word| !word.is_empty())
+            {
+                if !id.is_empty() {
+                    id.push('-');
+                }
+                id.extend(word.chars().flat_map(char::to_lowercase));
+            }
+        }
+
+        id
+    }
+
+    /// Forgets a mode that the compositor withdrew, and the current mode if it was that.
+    pub(crate) fn remove_mode(&mut self, id: &ObjectId) {
+        self.modes.shift_remove(id);
+
+        // Never leave the head referring to a mode that no longer exists.
+        if self.current_mode.as_ref() == Some(id) {
+            self.current_mode = None;
+        }
+    }
+
+    /// The lowest and highest refresh rates, in mHz, that adaptive sync may vary between.
+    ///
+    /// No protocol advertises the range of the panel, so it is bounded by the refresh
+    /// rates of the advertised modes, which the panel is known to support. Outputs
+    /// without adaptive sync have no range.
+    #[must_use]
+    pub fn vrr_range(&self) -> Option<(i32, i32)> {
+        if self.adaptive_sync_support == Some(AdaptiveSyncAvailability::Unsupported) {
+            return None;
+        }
+
+        let refresh_rates = self.modes.values().map(|mode| mode.refresh);
+        Some((refresh_rates.clone().min()?, refresh_rates.max()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remove_current_mode() {
+        let mut head = OutputHead::inert("DP-1");
+        let mode = OutputMode::new(Proxy::inert(head.wlr_head.backend().clone()));
+        let id = mode.wlr_mode.id();
+        head.modes.insert(id.clone(), mode);
+        head.current_mode = Some(id.clone());
+        assert!(head.current().is_some());
+
+        head.remove_mode(&id);
+        assert!(head.modes.is_empty());
+        assert_eq!(head.current_mode, None);
+    }
 }
@@ -57,7 +57,7 @@ impl Dispatch<ZwlrOutputModeV1, Mutex<Option<ObjectId>>> for Context {
                     proxy.release();
                 }
 
-                head.modes.shift_remove(&proxy.id());
+                head.remove_mode(&proxy.id());
             }
 
             _ => tracing::debug!(?event, "unknown event"),
@@ -78,6 +78,78 @@ impl OutputMode {
     }
 }
 
+impl From<&OutputMode> for cosmic_randr_shell::Mode {
+    fn from(mode: &OutputMode) -> Self {
+        Self {
+            size: (mode.width as u32, mode.height as u32),
+            refresh_rate: mode.refresh as u32,
+            preferred: mode.preferred,
+        }
+    }
+}
+
+/// Largest difference, in mHz, between a requested refresh rate and the mode chosen
+/// for it by [`select_mode`].
+///
+/// Refresh rates are derived from the pixel clock and timings of the mode, so a panel
+/// sold as 144 Hz may advertise 143.999 Hz, or 59.94 Hz for 60 Hz. Half a hertz accepts
+/// such rounding differences. Rates closer together than that, such as 59.94 Hz and
+/// 60 Hz, may both be within it, in which case the mode nearest to the request wins.
+pub const REFRESH_TOLERANCE: i32 = 500;
+
+/// The size and refresh rate by which [`select_mode`] tells modes apart.
+///
+/// Besides [`OutputMode`], this is implemented for `(width, height, refresh)` tuples, so
+/// that modes may be selected from lists that don't come from the compositor.
+pub trait ModeTiming {
+    /// Width and height of the mode, in pixels.
+    fn size(&self) -> (i32, i32);
+
+    /// Refresh rate of the mode, in mHz.
+    fn refresh(&self) -> i32;
+}
+
+impl ModeTiming for OutputMode {
+    fn size(&self) -> (i32, i32) {
+        (self.width, self.height)
+    }
+
+    fn refresh(&self) -> i32 {
+        self.refresh
+    }
+}
+
+impl ModeTiming for (i32, i32, i32) {
+    fn size(&self) -> (i32, i32) {
+        (self.0, self.1)
+    }
+
+    fn refresh(&self) -> i32 {
+        self.2
+    }
+}
+
+/// Selects the mode of the given size with the refresh rate nearest to `refresh`, in mHz.
+///
+/// Only modes within [`REFRESH_TOLERANCE`] of the requested rate are considered, and the
+/// numerically nearest of them is chosen, so a request for 144 Hz selects a 143.999 Hz
+/// mode rather than a 120 Hz one, and an exact match always wins. Of two equally near
+/// modes the first is chosen. Modes of any other size are never chosen.
+///
+/// This does not involve the compositor, so it may be used on any list of modes.
+#[must_use]
+pub fn select_mode<'a, M: ModeTiming>(
+    modes: impl IntoIterator<Item = &'a M>,
+    size: (i32, i32),
+    refresh: i32,
+) -> Option<&'a M> {
+    modes
+        .into_iter()
+        .filter(|mode| mode.size() == size)
+        .filter(|mode| (mode.refresh() - refresh).abs() <= REFRESH_TOLERANCE)
+        .min_by_key(|mode| (mode.refresh() - refresh).abs())
+}
+
 impl PartialOrd for OutputMode {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
@@ -93,3 +165,88 @@ impl Ord for OutputMode {
             .reverse()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODES: [(i32, i32, i32); 6] = [
+        (3840, 2160, 143_999),
+        (3840, 2160, 120_000),
+        (3840, 2160, 60_000),
+        (3840, 2160, 59_940),
+        (2560, 1440, 144_000),
+        (1920, 1080, 60_000),
+    ];
+
+    fn select(size: (i32, i32), refresh: i32) -> Option<(i32, i32, i32)> {
+        select_mode(&MODES, size, refresh).copied()
+    }
+
+    #[test]
+    fn exact_match() {
+        assert_eq!(select((3840, 2160), 120_000), Some((3840, 2160, 120_000)));
+        assert_eq!(select((3840, 2160), 59_940), Some((3840, 2160, 59_940)));
+        assert_eq!(select((2560, 1440), 144_000), Some((2560, 1440, 144_000)));
+    }
+
+    #[test]
+    fn nearest_within_tolerance() {
+        assert_eq!(select((3840, 2160), 119_800), Some((3840, 2160, 120_000)));
+        assert_eq!(select((3840, 2160), 59_960), Some((3840, 2160, 59_940)));
+        assert_eq!(select((3840, 2160), 59_980), Some((3840, 2160, 60_000)));
+        assert_eq!(
+            select((3840, 2160), 120_000 + REFRESH_TOLERANCE),
+            Some((3840, 2160, 120_000))
+        );
+    }
+
+    #[test]
+    fn tie_keeps_first_mode() {
+        let modes = [(1920, 1080, 59_800), (1920, 1080, 60_200)];
+        assert_eq!(
+            select_mode(&modes, (1920, 1080), 60_000),
+            Some(&(1920, 1080, 59_800))
+        );
+
+        let modes = [(1920, 1080, 60_200), (1920, 1080, 59_800)];
+        assert_eq!(
+            select_mode(&modes, (1920, 1080), 60_000),
+            Some(&(1920, 1080, 60_200))
+        );
+    }
+
+    #[test]
+    fn wrong_size() {
+        assert_eq!(select((2560, 1440), 60_000), None);
+        assert_eq!(select((2160, 3840), 60_000), None);
+        assert_eq!(select((1280, 720), 60_000), None);
+    }
+
+    #[test]
+    fn out_of_window() {
+        assert_eq!(select((3840, 2160), 100_000), None);
+        assert_eq!(select((3840, 2160), 120_000 + REFRESH_TOLERANCE + 1), None);
+        assert_eq!(select((2560, 1440), 143_000), None);
+    }
+
+    #[test]
+    fn whole_hertz_request_selects_fractional_mode() {
+        // 4K144 panels advertise 143.999 Hz next to 120 Hz, which must never be chosen
+        // for a request of 144 Hz.
+        let modes = [
+            (3840, 2160, 120_000),
+            (3840, 2160, 143_999),
+            (3840, 2160, 60_000),
+        ];
+
+        assert_eq!(
+            select_mode(&modes, (3840, 2160), 144_000),
+            Some(&(3840, 2160, 143_999))
+        );
+        assert_eq!(
+            select_mode(&modes, (3840, 2160), 143_999),
+            Some(&(3840, 2160, 143_999))
+        );
+    }
+}
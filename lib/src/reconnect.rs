@@ -0,0 +1,101 @@
+// Copyright 2023 System76 <info@system76.com>
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::{Context, Error, Message};
+use std::time::Duration;
+use tachyonix::Sender;
+use wayland_client::backend::WaylandError;
+use wayland_client::{DispatchError, EventQueue};
+
+/// A [`Context`] which reconnects to the compositor when the connection is lost.
+///
+/// Long-running embedders, such as panels and daemons, outlive the compositor when it
+/// restarts. Instead of returning the disconnect error from [`Self::dispatch`], the
+/// context is rebuilt and [`Message::Reconnected`] is sent once it is ready again.
+#[derive(Debug)]
+pub struct ReconnectingContext {
+    pub context: Context,
+    pub event_queue: EventQueue<Context>,
+    sender: Sender<Message>,
+
+    /// Time to wait before each attempt to reconnect.
+    pub retry_interval: Duration,
+    /// Number of attempts to reconnect before the error is returned.
+    pub max_attempts: u32,
+}
+
+impl ReconnectingContext {
+    /// Creates a wayland client connection which reconnects on disconnect.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if there are any wayland client connection errors.
+    pub fn connect(sender: Sender<Message>) -> Result<Self, Error> {
+        let (context, event_queue) = Context::connect(sender.clone())?;
+
+        Ok(Self {
+            context,
+            event_queue,
+            sender,
+            retry_interval: Duration::from_millis(500),
+            max_attempts: 20,
+        })
+    }
+
+    /// Dispatches events, reconnecting first if the compositor went away.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if dispatch fails for a reason other than a disconnect, or if
+    /// reconnecting did not succeed within `max_attempts`.
+    pub async fn dispatch(&mut self) -> Result<usize, Error> {
+        match self.context.dispatch(&mut self.event_queue).await {
+            Err(why) if is_disconnect(&why) => {
+                tracing::warn!("lost connection to the compositor: {why}");
+                self.reconnect().await?;
+                Ok(0)
+            }
+            result => result,
+        }
+    }
+
+    async fn reconnect(&mut self) -> Result<(), Error> {
+        let mut attempt = 0;
+
+        loop {
+            attempt += 1;
+            tokio::time::sleep(self.retry_interval).await;
+
+            match Context::connect(self.sender.clone()) {
+                Ok((context, event_queue)) => {
+                    self.context = context;
+                    self.event_queue = event_queue;
+                    let _res = self.context.send(Message::Reconnected).await;
+                    return Ok(());
+                }
+
+                Err(why) if attempt < self.max_attempts => {
+                    tracing::debug!("reconnect attempt {attempt} failed: {why}");
+                }
+
+                Err(why) => return Err(why),
+            }
+        }
+    }
+}
+
+/// Whether the error was caused by the compositor closing the connection.
+fn is_disconnect(error: &Error) -> bool {
+    let backend = match error {
+        Error::WaylandContext(why) | Error::WaylandDispatch(DispatchError::Backend(why)) => why,
+        _ => return false,
+    };
+
+    matches!(backend, WaylandError::Io(why) if matches!(
+        why.kind(),
+        std::io::ErrorKind::BrokenPipe
+            | std::io::ErrorKind::ConnectionReset
+            | std::io::ErrorKind::ConnectionAborted
+            | std::io::ErrorKind::UnexpectedEof
+    ))
+}
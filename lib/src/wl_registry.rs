@@ -1,9 +1,9 @@
 // Copyright 2023 System76 <info@system76.com>
 // SPDX-License-Identifier: MPL-2.0
 
-use crate::{Context, Message};
+use crate::{Context, CosmicCapabilities, Message};
 use cosmic_protocols::output_management::v1::client::zcosmic_output_manager_v1::ZcosmicOutputManagerV1;
-use wayland_client::{protocol::wl_registry, Connection, Dispatch, QueueHandle};
+use wayland_client::{protocol::wl_registry, Connection, Dispatch, Proxy, QueueHandle};
 use wayland_protocols_wlr::output_management::v1::client::zwlr_output_manager_v1::ZwlrOutputManagerV1;
 
 impl Dispatch<wl_registry::WlRegistry, ()> for Context {
@@ -42,13 +42,11 @@ impl Dispatch<wl_registry::WlRegistry, ()> for Context {
                     (),
                 ));
             }
-            if "zcosmic_output_manager_v1" == &interface[..] {
-                state.cosmic_output_manager = Some(registry.bind::<ZcosmicOutputManagerV1, _, _>(
-                    name,
-                    version.min(2),
-                    handle,
-                    (),
-                ))
+            if "zcosmic_output_manager_v1" == &interface[..] && !crate::cosmic_disabled() {
+                let manager =
+                    registry.bind::<ZcosmicOutputManagerV1, _, _>(name, version.min(2), handle, ());
+                state.cosmic_capabilities = CosmicCapabilities::new(manager.version());
+                state.cosmic_output_manager = Some(manager);
             }
         }
     }
@@ -1,9 +1,14 @@
 // Copyright 2023 System76 <info@system76.com>
 // SPDX-License-Identifier: MPL-2.0
 
+#[cfg(feature = "serde")]
+mod serialize;
+#[cfg(feature = "wayland")]
+mod wayland;
+
 use std::fmt::Display;
 
-use kdl::{KdlDocument, KdlError};
+use kdl::{KdlDocument, KdlEntry, KdlError, KdlNode};
 use slotmap::SlotMap;
 
 slotmap::new_key_type! {
@@ -13,7 +18,8 @@ slotmap::new_key_type! {
     pub struct ModeKey;
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Mode {
     pub size: (u32, u32),
     pub refresh_rate: u32,
@@ -29,29 +35,194 @@ impl Mode {
             preferred: false,
         }
     }
+
+    /// Refresh rate in hertz, from the millihertz of `refresh_rate`.
+    #[must_use]
+    pub fn refresh_hz(&self) -> f64 {
+        f64::from(self.refresh_rate) / 1000.0
+    }
+
+    /// Refresh rate rounded to the nearest whole hertz, such as 144 for 143.999 Hz.
+    #[must_use]
+    pub fn refresh_rounded(&self) -> u32 {
+        self.refresh_rate.saturating_add(500) / 1000
+    }
+
+    /// The `mode` node of the mode, as within the `modes` of `cosmic-randr list --kdl`.
+    #[must_use]
+    pub fn to_kdl(&self, current: bool) -> KdlNode {
+        let mut mode_node = node(
+            "mode",
+            [
+                i64::from(self.size.0).into(),
+                i64::from(self.size.1).into(),
+                i64::from(self.refresh_rate).into(),
+            ],
+        );
+
+        if current {
+            mode_node.push(KdlEntry::new_prop("current", true));
+        }
+
+        if self.preferred {
+            mode_node.push(KdlEntry::new_prop("preferred", true));
+        }
+
+        mode_node
+    }
 }
 
+/// Outputs and the modes that they reference.
+///
+/// With the `serde` feature, the list is serialized as an array of outputs, each with
+/// its own modes, and `current` as the index of the current mode within them. The keys
+/// are rebuilt on deserialization.
 #[derive(Clone, Debug, Default)]
 pub struct List {
     pub outputs: SlotMap<OutputKey, Output>,
     pub modes: SlotMap<ModeKey, Mode>,
 }
 
+/// An output, whose modes are stored in its [`List`].
+///
+/// The mode keys are only meaningful within the list, so they are skipped when the
+/// output is serialized on its own.
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Output {
     pub name: String,
+    /// Identifier of the display that is stable across connectors, when it is known.
+    pub id: Option<String>,
     pub enabled: bool,
     pub mirroring: Option<String>,
     pub make: Option<String>,
     pub model: String,
+    pub serial_number: Option<String>,
     pub physical: (u32, u32),
     pub position: (i32, i32),
     pub scale: f64,
     pub transform: Option<Transform>,
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub modes: Vec<ModeKey>,
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub current: Option<ModeKey>,
     pub adaptive_sync: Option<AdaptiveSyncState>,
     pub adaptive_sync_availability: Option<AdaptiveSyncAvailability>,
+    /// Lowest and highest refresh rates, in mHz, that adaptive sync may vary between.
+    pub vrr_range: Option<(u32, u32)>,
+    pub capabilities: Option<Capabilities>,
+}
+
+/// Features of an output that the compositor allows to be configured.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Capabilities {
+    /// Adaptive sync can be enabled and disabled.
+    pub adaptive_sync: bool,
+    /// Adaptive sync can be left to the compositor to manage.
+    pub automatic_adaptive_sync: bool,
+    /// Scales that aren't whole numbers are applied exactly.
+    pub fractional_scale: bool,
+    /// The output can mirror another output.
+    pub mirroring: bool,
+    /// The output can be rotated and flipped.
+    pub transform: bool,
+    /// Modes other than those advertised can be requested.
+    pub custom_modes: bool,
+}
+
+impl Capabilities {
+    /// Each capability with the name it has in the KDL format.
+    #[must_use]
+    pub fn entries(self) -> [(&'static str, bool); 6] {
+        [
+            ("adaptive_sync", self.adaptive_sync),
+            ("automatic_adaptive_sync", self.automatic_adaptive_sync),
+            ("fractional_scale", self.fractional_scale),
+            ("mirroring", self.mirroring),
+            ("transform", self.transform),
+            ("custom_modes", self.custom_modes),
+        ]
+    }
+
+    fn set(&mut self, name: &str, value: bool) {
+        match name {
+            "adaptive_sync" => self.adaptive_sync = value,
+            "automatic_adaptive_sync" => self.automatic_adaptive_sync = value,
+            "fractional_scale" => self.fractional_scale = value,
+            "mirroring" => self.mirroring = value,
+            "transform" => self.transform = value,
+            "custom_modes" => self.custom_modes = value,
+            _ => (),
+        }
+    }
+}
+
+impl PartialEq for List {
+    /// Lists are equal when they describe the same outputs with the same modes,
+    /// regardless of the order or keys that these were stored with.
+    fn eq(&self, other: &Self) -> bool {
+        self.outputs.len() == other.outputs.len()
+            && self.outputs.values().all(|output| {
+                other
+                    .output_by_name(&output.name)
+                    .is_some_and(|theirs| self.output_eq(output, other, theirs))
+            })
+    }
+}
+
+impl List {
+    /// The output connected to the connector of this name, such as `DP-1`.
+    ///
+    /// Unlike the slotmap keys, connector names are preserved when a list is written
+    /// to KDL and parsed again.
+    #[must_use]
+    pub fn output_by_name(&self, name: &str) -> Option<&Output> {
+        self.outputs.values().find(|output| output.name == name)
+    }
+
+    /// The mode that the output is currently set to.
+    #[must_use]
+    pub fn current_mode(&self, output: &Output) -> Option<&Mode> {
+        output.current.and_then(|key| self.modes.get(key))
+    }
+
+    /// The mode that the output advertises as preferred.
+    #[must_use]
+    pub fn preferred_mode(&self, output: &Output) -> Option<&Mode> {
+        output
+            .modes
+            .iter()
+            .filter_map(|key| self.modes.get(*key))
+            .find(|mode| mode.preferred)
+    }
+
+    fn output_eq(&self, output: &Output, other: &Self, theirs: &Output) -> bool {
+        let modes = |list: &Self, output: &Output| {
+            output
+                .modes
+                .iter()
+                .map(|key| list.modes.get(*key))
+                .collect::<Vec<_>>()
+        };
+
+        output.id == theirs.id
+            && output.enabled == theirs.enabled
+            && output.mirroring == theirs.mirroring
+            && output.make == theirs.make
+            && output.model == theirs.model
+            && output.serial_number == theirs.serial_number
+            && output.physical == theirs.physical
+            && output.position == theirs.position
+            && output.scale == theirs.scale
+            && output.transform == theirs.transform
+            && output.adaptive_sync == theirs.adaptive_sync
+            && output.adaptive_sync_availability == theirs.adaptive_sync_availability
+            && output.vrr_range == theirs.vrr_range
+            && output.capabilities == theirs.capabilities
+            && self.current_mode(output) == other.current_mode(theirs)
+            && modes(self, output) == modes(other, theirs)
+    }
 }
 
 impl Output {
@@ -59,10 +230,12 @@ impl Output {
     pub const fn new() -> Self {
         Self {
             name: String::new(),
+            id: None,
             enabled: false,
             mirroring: None,
             make: None,
             model: String::new(),
+            serial_number: None,
             physical: (0, 0),
             position: (0, 0),
             scale: 1.0,
@@ -71,11 +244,19 @@ impl Output {
             current: None,
             adaptive_sync: None,
             adaptive_sync_availability: None,
+            vrr_range: None,
+            capabilities: None,
         }
     }
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
 pub enum Transform {
     Normal,
     Rotate90,
@@ -120,10 +301,91 @@ impl TryFrom<&str> for Transform {
     }
 }
 
+impl Transform {
+    /// Composes `other` on top of `self`, as if both were applied in sequence.
+    #[must_use]
+    pub fn compose(self, other: Self) -> Self {
+        let (flip_a, rot_a) = self.parts();
+        let (flip_b, rot_b) = other.parts();
+
+        // Flipping reverses the direction of any rotation applied before it.
+        let rotation = if flip_b {
+            (rot_b + 4 - rot_a) % 4
+        } else {
+            (rot_b + rot_a) % 4
+        };
+
+        Self::from_parts(flip_a ^ flip_b, rotation)
+    }
+
+    /// Whether the transform keeps the width and height of the output in place.
+    ///
+    /// This is the case for [`Transform::Normal`], [`Transform::Rotate180`],
+    /// [`Transform::Flipped`] and [`Transform::Flipped180`], as flipping mirrors the
+    /// output without turning it.
+    #[must_use]
+    pub fn is_landscape(self) -> bool {
+        self.parts().1 % 2 == 0
+    }
+
+    /// Whether the transform exchanges the width and height of the output, as the
+    /// quarter turns of [`Transform::Rotate90`], [`Transform::Rotate270`],
+    /// [`Transform::Flipped90`] and [`Transform::Flipped270`] do.
+    #[must_use]
+    pub fn swaps_dimensions(self) -> bool {
+        !self.is_landscape()
+    }
+
+    /// The size of a mode once the transform is applied to it.
+    #[must_use]
+    pub fn apply_to_size<T>(self, (width, height): (T, T)) -> (T, T) {
+        if self.swaps_dimensions() {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
+
+    /// Splits the transform into a flip and a number of counter-clockwise quarter turns.
+    fn parts(self) -> (bool, u8) {
+        match self {
+            Transform::Normal => (false, 0),
+            Transform::Rotate90 => (false, 1),
+            Transform::Rotate180 => (false, 2),
+            Transform::Rotate270 => (false, 3),
+            Transform::Flipped => (true, 0),
+            Transform::Flipped90 => (true, 1),
+            Transform::Flipped180 => (true, 2),
+            Transform::Flipped270 => (true, 3),
+        }
+    }
+
+    fn from_parts(flipped: bool, rotation: u8) -> Self {
+        match (flipped, rotation % 4) {
+            (false, 0) => Transform::Normal,
+            (false, 1) => Transform::Rotate90,
+            (false, 2) => Transform::Rotate180,
+            (false, _) => Transform::Rotate270,
+            (true, 0) => Transform::Flipped,
+            (true, 1) => Transform::Flipped90,
+            (true, 2) => Transform::Flipped180,
+            (true, _) => Transform::Flipped270,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AdaptiveSyncState {
+    #[cfg_attr(feature = "clap", value(name = "true"))]
+    #[cfg_attr(feature = "serde", serde(rename = "true"))]
     Always,
+    #[cfg_attr(feature = "clap", value(name = "automatic"))]
+    #[cfg_attr(feature = "serde", serde(rename = "automatic"))]
     Auto,
+    #[cfg_attr(feature = "clap", value(name = "false"))]
+    #[cfg_attr(feature = "serde", serde(rename = "false"))]
     Disabled,
 }
 
@@ -151,9 +413,13 @@ impl TryFrom<&str> for AdaptiveSyncState {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AdaptiveSyncAvailability {
+    #[cfg_attr(feature = "serde", serde(rename = "true"))]
     Supported,
+    #[cfg_attr(feature = "serde", serde(rename = "requires_modeset"))]
     RequiresModeset,
+    #[cfg_attr(feature = "serde", serde(rename = "false"))]
     Unsupported,
 }
 
@@ -180,208 +446,667 @@ impl TryFrom<&str> for AdaptiveSyncAvailability {
     }
 }
 
-#[derive(thiserror::Error, Debug)]
-pub enum Error {
-    #[error("`cosmic-randr` KDL format error")]
-    Kdl(#[from] KdlError),
-    #[error("could not exec `cosmic-randr`")]
-    Spawn(#[source] std::io::Error),
-    #[error("`cosmic-randr` output not UTF-8")]
-    Utf(#[from] std::str::Utf8Error),
+impl TryFrom<KdlDocument> for List {
+    type Error = KdlParseErrors;
+
+    /// Parses the format printed by `cosmic-randr list --kdl`, ignoring nodes that it
+    /// doesn't know, as newer versions of `cosmic-randr` may add them.
+    fn try_from(document: KdlDocument) -> Result<Self, Self::Error> {
+        Self::from_kdl(document, false)
+    }
 }
 
-#[allow(clippy::too_many_lines)]
-pub async fn list() -> Result<List, Error> {
-    // Get a list of outputs from `cosmic-randr` in KDL format.
-    let stdout = std::process::Command::new("cosmic-randr")
-        .args(&["list", "--kdl"])
-        .stdin(std::process::Stdio::null())
-        .stdout(std::process::Stdio::piped())
-        .stderr(std::process::Stdio::null())
-        .output()
-        .map_err(Error::Spawn)?
-        .stdout;
+impl List {
+    /// Parses the format printed by `cosmic-randr list --kdl`.
+    ///
+    /// Unknown nodes are only reported as [`KdlParseError::InvalidKey`] if `strict` is
+    /// set, such as to catch typos in a layout written by hand. Outputs without a name
+    /// or properties are always reported.
+    ///
+    /// # Errors
+    ///
+    /// Returns every problem found, along with the outputs that could still be parsed.
+    #[allow(clippy::too_many_lines)]
+    pub fn from_kdl(document: KdlDocument, strict: bool) -> Result<Self, KdlParseErrors> {
+        let mut errors = Vec::new();
+        let mut outputs = List {
+            outputs: SlotMap::with_key(),
+            modes: SlotMap::with_key(),
+        };
 
-    // Parse the output as a KDL document.
-    let document = std::str::from_utf8(&stdout)
-        .map_err(Error::Utf)?
-        .parse::<KdlDocument>()
-        .map_err(Error::Kdl)?;
-
-    let mut outputs = List {
-        outputs: SlotMap::with_key(),
-        modes: SlotMap::with_key(),
-    };
+        // Each node in the root of the document is an output.
+        for node in document.nodes() {
+            if node.name().value() != "output" {
+                if strict {
+                    errors.push(KdlParseError::InvalidKey(node.name().value().to_owned()));
+                }
+                continue;
+            }
 
-    // Each node in the root of the document is an output.
-    for node in document.nodes() {
-        if node.name().value() != "output" {
-            eprintln!("not output");
-            continue;
-        }
+            // Parse the properties of the output mode
+            let entries = node.entries().iter();
 
-        // Parse the properties of the output mode
-        let mut entries = node.entries().iter();
+            // The first argument is the name of the output, which the `connector`
+            // attribute repeats for documents that are written by hand.
+            let mut name = entries
+                .clone()
+                .find(|e| e.name().is_none())
+                .and_then(|e| e.value().as_string());
 
-        // The first value is the name of the otuput
-        let Some(name) = entries.next().and_then(|e| e.value().as_string()) else {
-            eprintln!("no name value");
-            continue;
-        };
+            let mut output = Output::new();
 
-        let mut output = Output::new();
+            // Check if the output contains the `enabled` and `connector` attributes.
+            for entry in entries {
+                let Some(entry_name) = entry.name() else {
+                    continue;
+                };
 
-        // Check if the output contains the `enabled` attribute.
-        for entry in entries {
-            let Some(entry_name) = entry.name() else {
-                continue;
-            };
+                match entry_name.value() {
+                    "enabled" => {
+                        if let Some(enabled) = entry.value().as_bool() {
+                            output.enabled = enabled;
+                        }
+                    }
+
+                    "connector" => {
+                        name = name.or_else(|| entry.value().as_string());
+                    }
 
-            if entry_name.value() == "enabled" {
-                if let Some(enabled) = entry.value().as_bool() {
-                    output.enabled = enabled;
+                    _ => (),
                 }
             }
-        }
 
-        // Gets the properties of the output.
-        let Some(children) = node.children() else {
-            continue;
-        };
+            let Some(name) = name else {
+                errors.push(KdlParseError::MissingName);
+                continue;
+            };
+
+            // Gets the properties of the output.
+            let Some(children) = node.children() else {
+                errors.push(KdlParseError::MissingChildren(name.to_owned()));
+                continue;
+            };
 
-        for node in children.nodes() {
-            match node.name().value() {
-                // Parse the make and model of the display output.
-                "description" => {
-                    for entry in node.entries() {
-                        let value = entry.value().as_string();
+            for node in children.nodes() {
+                match node.name().value() {
+                    // Parse the make and model of the display output.
+                    "description" => {
+                        for entry in node.entries() {
+                            let value = entry.value().as_string();
 
-                        match entry.name().map(kdl::KdlIdentifier::value) {
-                            Some("make") => {
-                                output.make = value.map(String::from);
-                            }
+                            match entry.name().map(kdl::KdlIdentifier::value) {
+                                Some("make") => {
+                                    output.make = value.map(String::from);
+                                }
 
-                            Some("model") => {
-                                if let Some(model) = value {
-                                    output.model = String::from(model);
+                                Some("model") => {
+                                    if let Some(model) = value {
+                                        output.model = String::from(model);
+                                    }
                                 }
+
+                                _ => (),
                             }
+                        }
+                    }
 
-                            _ => (),
+                    // Parse the physical width and height in millimeters
+                    "physical" => {
+                        if let [width, height, ..] = node.entries() {
+                            output.physical = (
+                                width.value().as_i64().unwrap_or_default() as u32,
+                                height.value().as_i64().unwrap_or_default() as u32,
+                            );
                         }
                     }
-                }
 
-                // Parse the physical width and height in millimeters
-                "physical" => {
-                    if let [width, height, ..] = node.entries() {
-                        output.physical = (
-                            width.value().as_i64().unwrap_or_default() as u32,
-                            height.value().as_i64().unwrap_or_default() as u32,
-                        );
+                    // Parse the pixel coordinates of the output.
+                    "position" => {
+                        if let [x_pos, y_pos, ..] = node.entries() {
+                            output.position = (
+                                x_pos.value().as_i64().unwrap_or_default() as i32,
+                                y_pos.value().as_i64().unwrap_or_default() as i32,
+                            );
+                        }
                     }
-                }
 
-                // Parse the pixel coordinates of the output.
-                "position" => {
-                    if let [x_pos, y_pos, ..] = node.entries() {
-                        output.position = (
-                            x_pos.value().as_i64().unwrap_or_default() as i32,
-                            y_pos.value().as_i64().unwrap_or_default() as i32,
-                        );
+                    "scale" => {
+                        if let Some(entry) = node.entries().first() {
+                            if let Some(scale) = entry.value().as_f64() {
+                                output.scale = scale;
+                            }
+                        }
                     }
-                }
 
-                "scale" => {
-                    if let Some(entry) = node.entries().first() {
-                        if let Some(scale) = entry.value().as_f64() {
-                            output.scale = scale;
+                    // Parse the transform value of the output.
+                    "transform" => {
+                        if let Some(entry) = node.entries().first() {
+                            if let Some(string) = entry.value().as_string() {
+                                output.transform = Transform::try_from(string).ok();
+                            }
                         }
                     }
-                }
 
-                // Parse the transform value of the output.
-                "transform" => {
-                    if let Some(entry) = node.entries().first() {
-                        if let Some(string) = entry.value().as_string() {
-                            output.transform = Transform::try_from(string).ok();
+                    "adaptive_sync" => {
+                        if let Some(entry) = node.entries().first() {
+                            if let Some(string) = entry.value().as_string() {
+                                output.adaptive_sync = AdaptiveSyncState::try_from(string).ok();
+                            }
                         }
                     }
-                }
 
-                "adaptive_sync" => {
-                    if let Some(entry) = node.entries().first() {
-                        if let Some(string) = entry.value().as_string() {
-                            output.adaptive_sync = AdaptiveSyncState::try_from(string).ok();
+                    "adaptive_sync_support" => {
+                        if let Some(entry) = node.entries().first() {
+                            if let Some(string) = entry.value().as_string() {
+                                output.adaptive_sync_availability =
+                                    AdaptiveSyncAvailability::try_from(string).ok();
+                            }
                         }
                     }
-                }
 
-                "adaptive_sync_support" => {
-                    if let Some(entry) = node.entries().first() {
-                        if let Some(string) = entry.value().as_string() {
-                            output.adaptive_sync_availability =
-                                AdaptiveSyncAvailability::try_from(string).ok();
+                    // Parse the refresh rate range of adaptive sync, in millihertz.
+                    "vrr_range" => {
+                        if let [min, max, ..] = node.entries() {
+                            output.vrr_range = Some((
+                                min.value().as_i64().unwrap_or_default() as u32,
+                                max.value().as_i64().unwrap_or_default() as u32,
+                            ));
                         }
                     }
-                }
 
-                // Switch to parsing output modes.
-                "modes" => {
-                    let Some(children) = node.children() else {
-                        continue;
-                    };
-
-                    for node in children.nodes() {
-                        if node.name().value() == "mode" {
-                            let mut current = false;
-                            let mut mode = Mode::new();
-
-                            if let [width, height, refresh, ..] = node.entries() {
-                                mode.size = (
-                                    width.value().as_i64().unwrap_or_default() as u32,
-                                    height.value().as_i64().unwrap_or_default() as u32,
-                                );
-
-                                mode.refresh_rate =
-                                    refresh.value().as_i64().unwrap_or_default() as u32;
-                            };
-
-                            for entry in node.entries().iter().skip(3) {
-                                match entry.name().map(kdl::KdlIdentifier::value) {
-                                    Some("current") => current = true,
-                                    Some("preferred") => mode.preferred = true,
-                                    _ => (),
+                    "capabilities" => {
+                        let mut capabilities = Capabilities::default();
+
+                        for entry in node.entries() {
+                            if let (Some(name), Some(value)) =
+                                (entry.name(), entry.value().as_bool())
+                            {
+                                capabilities.set(name.value(), value);
+                            }
+                        }
+
+                        output.capabilities = Some(capabilities);
+                    }
+
+                    // Switch to parsing output modes.
+                    "modes" => {
+                        let Some(children) = node.children() else {
+                            continue;
+                        };
+
+                        for node in children.nodes() {
+                            if node.name().value() == "mode" {
+                                let mut current = false;
+                                let mut mode = Mode::new();
+
+                                if let [width, height, refresh, ..] = node.entries() {
+                                    mode.size = (
+                                        width.value().as_i64().unwrap_or_default() as u32,
+                                        height.value().as_i64().unwrap_or_default() as u32,
+                                    );
+
+                                    mode.refresh_rate =
+                                        refresh.value().as_i64().unwrap_or_default() as u32;
+                                };
+
+                                for entry in node.entries().iter().skip(3) {
+                                    match entry.name().map(kdl::KdlIdentifier::value) {
+                                        Some("current") => current = true,
+                                        Some("preferred") => mode.preferred = true,
+                                        _ => (),
+                                    }
                                 }
+
+                                let mode_id = outputs.modes.insert(mode);
+
+                                if current {
+                                    output.current = Some(mode_id);
+                                }
+
+                                output.modes.push(mode_id);
                             }
+                        }
+                    }
 
-                            let mode_id = outputs.modes.insert(mode);
+                    "mirroring" => {
+                        if let Some(entry) = node.entries().first() {
+                            if let Some(string) = entry.value().as_string() {
+                                output.mirroring = Some(string.to_string());
+                            }
+                        }
+                    }
 
-                            if current {
-                                output.current = Some(mode_id);
+                    "id" => {
+                        if let Some(entry) = node.entries().first() {
+                            if let Some(string) = entry.value().as_string() {
+                                output.id = Some(string.to_string());
                             }
+                        }
+                    }
 
-                            output.modes.push(mode_id);
+                    "serial_number" => {
+                        if let Some(entry) = node.entries().first() {
+                            if let Some(string) = entry.value().as_string() {
+                                output.serial_number = Some(string.to_string());
+                            }
                         }
                     }
-                }
 
-                "mirroring" => {
-                    if let Some(entry) = node.entries().first() {
-                        if let Some(string) = entry.value().as_string() {
-                            output.mirroring = Some(string.to_string());
+                    key => {
+                        if strict {
+                            errors.push(KdlParseError::InvalidKey(key.to_owned()));
                         }
                     }
                 }
+            }
+
+            output.name = name.to_owned();
+
+            outputs.outputs.insert(output);
+        }
+
+        if errors.is_empty() {
+            Ok(outputs)
+        } else {
+            Err(KdlParseErrors {
+                list: outputs,
+                errors,
+            })
+        }
+    }
+}
+
+impl From<List> for KdlDocument {
+    /// Writes the list in the format printed by `cosmic-randr list --kdl`.
+    fn from(list: List) -> Self {
+        let mut document = KdlDocument::new();
+
+        for output in list.outputs.values() {
+            let mut output_node = KdlNode::new("output");
+            output_node.push(KdlEntry::new(output.name.as_str()));
+            output_node.push(KdlEntry::new_prop("connector", output.name.as_str()));
+            output_node.push(KdlEntry::new_prop("enabled", output.enabled));
+
+            let children = output_node.ensure_children().nodes_mut();
+
+            let mut description = KdlNode::new("description");
+            if let Some(make) = output.make.as_deref() {
+                description.push(KdlEntry::new_prop("make", make));
+            }
+            description.push(KdlEntry::new_prop("model", output.model.as_str()));
+            children.push(description);
+
+            children.push(node(
+                "physical",
+                [
+                    i64::from(output.physical.0).into(),
+                    i64::from(output.physical.1).into(),
+                ],
+            ));
+
+            children.push(node(
+                "position",
+                [
+                    i64::from(output.position.0).into(),
+                    i64::from(output.position.1).into(),
+                ],
+            ));
+
+            children.push(node("scale", [output.scale.into()]));
+
+            if let Some(mirroring) = output.mirroring.as_deref() {
+                children.push(node("mirroring", [mirroring.into()]));
+            }
+
+            if let Some(transform) = output.transform {
+                children.push(node("transform", [transform.to_string().into()]));
+            }
+
+            if let Some(available) = output.adaptive_sync_availability {
+                children.push(node(
+                    "adaptive_sync_support",
+                    [available.to_string().into()],
+                ));
+            }
+
+            if let Some(sync) = output.adaptive_sync {
+                children.push(node("adaptive_sync", [sync.to_string().into()]));
+            }
+
+            if let Some((min, max)) = output.vrr_range {
+                children.push(node(
+                    "vrr_range",
+                    [i64::from(min).into(), i64::from(max).into()],
+                ));
+            }
+
+            if let Some(serial_number) = output.serial_number.as_deref() {
+                children.push(node("serial_number", [serial_number.into()]));
+            }
+
+            if let Some(id) = output.id.as_deref() {
+                children.push(node("id", [id.into()]));
+            }
+
+            if let Some(capabilities) = output.capabilities {
+                let mut capabilities_node = KdlNode::new("capabilities");
+                for (name, value) in capabilities.entries() {
+                    capabilities_node.push(KdlEntry::new_prop(name, value));
+                }
+                children.push(capabilities_node);
+            }
+
+            let mut modes_node = KdlNode::new("modes");
+            let modes = modes_node.ensure_children().nodes_mut();
+
+            for &mode_key in &output.modes {
+                if let Some(mode) = list.modes.get(mode_key) {
+                    modes.push(mode.to_kdl(output.current == Some(mode_key)));
+                }
+            }
+
+            children.push(modes_node);
+            document.nodes_mut().push(output_node);
+        }
+
+        document.fmt();
+        document
+    }
+}
+
+/// Creates a node with positional arguments.
+fn node<const N: usize>(name: &str, arguments: [kdl::KdlValue; N]) -> KdlNode {
+    let mut node = KdlNode::new(name);
+    for argument in arguments {
+        node.push(KdlEntry::new(argument));
+    }
+    node
+}
+
+/// A problem found while parsing the KDL of a [`List`].
+#[derive(thiserror::Error, Debug)]
+pub enum KdlParseError {
+    #[error("unknown KDL node `{0}`")]
+    InvalidKey(String),
+    #[error("output node has no name")]
+    MissingName,
+    #[error("output `{0}` has no properties")]
+    MissingChildren(String),
+}
+
+/// Every problem found while parsing a [`List`], along with the outputs that could
+/// still be parsed.
+#[derive(thiserror::Error, Debug)]
+#[error("`cosmic-randr` KDL document has {} problem(s)", errors.len())]
+pub struct KdlParseErrors {
+    pub list: List,
+    pub errors: Vec<KdlParseError>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("`cosmic-randr` KDL format error")]
+    Kdl(#[from] KdlError),
+    /// The output of `cosmic-randr list --kdl` is not valid KDL.
+    #[error("`cosmic-randr` KDL format error")]
+    InvalidKdl {
+        #[source]
+        source: KdlError,
+        /// The output of `cosmic-randr list --kdl` which could not be parsed.
+        raw: String,
+    },
+    #[error("could not exec `cosmic-randr`")]
+    Spawn(#[source] std::io::Error),
+    #[error("`cosmic-randr` output not UTF-8")]
+    Utf(#[from] std::str::Utf8Error),
+}
+
+impl Error {
+    /// Whether `cosmic-randr` could not be run because it is not installed, or not in
+    /// the `PATH`, so that applications can suggest installing it.
+    #[must_use]
+    pub fn is_not_installed(&self) -> bool {
+        self.spawn_error_kind() == Some(std::io::ErrorKind::NotFound)
+    }
+
+    /// Kind of the I/O error that prevented `cosmic-randr` from being run, if it could
+    /// not be run.
+    #[must_use]
+    pub fn spawn_error_kind(&self) -> Option<std::io::ErrorKind> {
+        match self {
+            Self::Spawn(why) => Some(why.kind()),
+            _ => None,
+        }
+    }
+}
+
+/// Gets the outputs from `cosmic-randr list --kdl` as KDL, without parsing it.
+///
+/// # Errors
+///
+/// Returns error if `cosmic-randr` cannot be run or prints invalid UTF-8. Use
+/// [`Error::is_not_installed`] to tell whether it is missing.
+pub async fn list_raw() -> Result<String, Error> {
+    let stdout = std::process::Command::new("cosmic-randr")
+        .args(&["list", "--kdl"])
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::null())
+        .output()
+        .map_err(Error::Spawn)?
+        .stdout;
+
+    String::from_utf8(stdout).map_err(|why| Error::Utf(why.utf8_error()))
+}
 
-                _ => (),
+/// Gets the outputs from `cosmic-randr list --kdl`.
+///
+/// # Errors
+///
+/// Returns error if `cosmic-randr` cannot be run, which [`Error::is_not_installed`]
+/// tells apart when it is missing, or if its output is not valid KDL. Outputs that
+/// can't be parsed are skipped, and logged at the debug level.
+pub async fn list() -> Result<List, Error> {
+    let raw = list_raw().await?;
+
+    // Parse the output as a KDL document.
+    let document = match raw.parse::<KdlDocument>() {
+        Ok(document) => document,
+        Err(source) => return Err(Error::InvalidKdl { source, raw }),
+    };
+
+    match List::try_from(document) {
+        Ok(list) => Ok(list),
+        Err(KdlParseErrors { list, errors }) => {
+            for why in errors {
+                tracing::debug!("{why}");
             }
+
+            Ok(list)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TWO_MODES: &str = r#"
+output "DP-1" enabled=true {
+    description make="Dell Inc." model="DELL U2723QE"
+    physical 597 336
+    position 0 0
+    scale 1.5
+    modes {
+        mode 3840 2160 59997 preferred=true
+        mode 2560 1440 59951 current=true
+    }
+}
+"#;
+
+    fn parse(kdl: &str) -> List {
+        List::from_kdl(kdl.parse().unwrap(), true).unwrap()
+    }
+
+    #[test]
+    fn current_and_preferred_mode() {
+        let list = parse(TWO_MODES);
+        let output = list.output_by_name("DP-1").unwrap();
+
+        let current = list.current_mode(output).unwrap();
+        assert_eq!(current.size, (2560, 1440));
+        assert_eq!(current.refresh_rate, 59951);
+
+        let preferred = list.preferred_mode(output).unwrap();
+        assert_eq!(preferred.size, (3840, 2160));
+        assert_eq!(preferred.refresh_rate, 59997);
+    }
+
+    #[test]
+    fn no_current_or_preferred_mode() {
+        let mut list = parse(TWO_MODES);
+        let output = list.outputs.values_mut().next().unwrap();
+        output.current = None;
+        let modes = output.modes.clone();
+        for key in modes {
+            list.modes[key].preferred = false;
+        }
+
+        let output = list.output_by_name("DP-1").unwrap();
+        assert_eq!(list.current_mode(output), None);
+        assert_eq!(list.preferred_mode(output), None);
+    }
+
+    const EVERY_NODE: &str = r#"
+output "DP-1" enabled=true {
+    description make="Dell Inc." model="DELL U2723QE"
+    physical 597 336
+    position 0 0
+    scale 1.5
+    transform "rotate90"
+    adaptive_sync_support "true"
+    adaptive_sync "automatic"
+    vrr_range 48000 144000
+    serial_number "ABC123"
+    id "dell-inc-dell-u2723qe-abc123"
+    capabilities adaptive_sync=true automatic_adaptive_sync=true fractional_scale=true mirroring=true transform=true custom_modes=false
+    modes {
+        mode 3840 2160 59997 preferred=true
+        mode 2560 1440 59951 current=true
+    }
+}
+output "HDMI-A-1" enabled=true {
+    description model="LG TV"
+    physical 0 0
+    position 1440 0
+    scale 1.0
+    mirroring "DP-1"
+    modes {
+        mode 1920 1080 60000 current=true
+    }
+}
+"#;
+
+    #[test]
+    fn kdl_round_trip() {
+        let list = parse(EVERY_NODE);
+        let output = list.output_by_name("DP-1").unwrap();
+        assert!(output.id.is_some() && output.serial_number.is_some());
+        assert!(output.vrr_range.is_some() && output.capabilities.is_some());
+        assert_eq!(output.transform, Some(Transform::Rotate90));
+        let mirror = list.output_by_name("HDMI-A-1").unwrap();
+        assert_eq!(mirror.mirroring.as_deref(), Some("DP-1"));
+
+        assert_eq!(
+            List::from_kdl(KdlDocument::from(list.clone()), true).unwrap(),
+            list
+        );
+    }
+
+    const UNKNOWN_NODE: &str = r#"
+output "DP-1" enabled=true {
+    hdr true
+    position 0 0
+}
+"#;
+
+    #[test]
+    fn unknown_node_lenient() {
+        let list = List::from_kdl(UNKNOWN_NODE.parse().unwrap(), false).unwrap();
+        let output = list.output_by_name("DP-1").unwrap();
+        assert!(output.enabled);
+        assert_eq!(output.position, (0, 0));
+    }
+
+    #[test]
+    fn unknown_node_strict() {
+        let errors = List::from_kdl(UNKNOWN_NODE.parse().unwrap(), true).unwrap_err();
+        assert!(matches!(
+            errors.errors.as_slice(),
+            [KdlParseError::InvalidKey(key)] if key == "hdr"
+        ));
+        assert!(errors.list.output_by_name("DP-1").is_some());
+    }
+
+    /// Every transform, whether it swaps the dimensions of the output, and the transform
+    /// that undoes it.
+    const TRANSFORMS: [(Transform, bool, Transform); 8] = [
+        (Transform::Normal, false, Transform::Normal),
+        (Transform::Rotate90, true, Transform::Rotate270),
+        (Transform::Rotate180, false, Transform::Rotate180),
+        (Transform::Rotate270, true, Transform::Rotate90),
+        (Transform::Flipped, false, Transform::Flipped),
+        (Transform::Flipped90, true, Transform::Flipped90),
+        (Transform::Flipped180, false, Transform::Flipped180),
+        (Transform::Flipped270, true, Transform::Flipped270),
+    ];
+
+    #[test]
+    fn transform_dimensions() {
+        for (transform, swaps, _) in TRANSFORMS {
+            assert_eq!(transform.swaps_dimensions(), swaps, "{transform}");
+            assert_eq!(transform.is_landscape(), !swaps, "{transform}");
+
+            let size = if swaps { (1080, 1920) } else { (1920, 1080) };
+            assert_eq!(transform.apply_to_size((1920, 1080)), size, "{transform}");
         }
+    }
 
-        output.name = name.to_owned();
+    #[test]
+    fn transform_compose() {
+        assert_eq!(
+            Transform::Rotate90.compose(Transform::Rotate90),
+            Transform::Rotate180
+        );
+        assert_eq!(
+            Transform::Rotate180.compose(Transform::Rotate270),
+            Transform::Rotate90
+        );
+        assert_eq!(
+            Transform::Flipped.compose(Transform::Rotate90),
+            Transform::Flipped90
+        );
+        assert_eq!(
+            Transform::Rotate90.compose(Transform::Flipped),
+            Transform::Flipped270
+        );
 
-        outputs.outputs.insert(output);
+        for (transform, _, inverse) in TRANSFORMS {
+            assert_eq!(transform.compose(Transform::Normal), transform);
+            assert_eq!(Transform::Normal.compose(transform), transform);
+            assert_eq!(transform.compose(inverse), Transform::Normal, "{transform}");
+            assert_eq!(inverse.compose(transform), Transform::Normal, "{transform}");
+        }
     }
 
-    Ok(outputs)
+    #[test]
+    fn transform_compose_is_associative() {
+        for (a, ..) in TRANSFORMS {
+            for (b, ..) in TRANSFORMS {
+                for (c, ..) in TRANSFORMS {
+                    assert_eq!(a.compose(b).compose(c), a.compose(b.compose(c)));
+                }
+            }
+        }
+    }
 }
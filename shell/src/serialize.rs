@@ -0,0 +1,184 @@
+// Copyright 2023 System76 <info@system76.com>
+// SPDX-License-Identifier: MPL-2.0
+
+//! Serde support for [`List`], with the modes of each output stored alongside it.
+
+use crate::{List, Mode, Output};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+#[derive(Serialize)]
+struct ListRef<'a> {
+    outputs: Vec<OutputRef<'a>>,
+}
+
+#[derive(Serialize)]
+struct OutputRef<'a> {
+    #[serde(flatten)]
+    output: &'a Output,
+    modes: Vec<&'a Mode>,
+    current: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct OwnedList {
+    outputs: Vec<OwnedOutput>,
+}
+
+#[derive(Deserialize)]
+struct OwnedOutput {
+    #[serde(flatten)]
+    output: Output,
+    #[serde(default)]
+    modes: Vec<Mode>,
+    #[serde(default)]
+    current: Option<usize>,
+}
+
+impl Serialize for List {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let outputs = self
+            .outputs
+            .values()
+            .map(|output| {
+                let modes = output
+                    .modes
+                    .iter()
+                    .filter_map(|key| self.modes.get(*key))
+                    .collect();
+
+                let current = output.current.and_then(|current| {
+                    output
+                        .modes
+                        .iter()
+                        .filter(|key| self.modes.contains_key(**key))
+                        .position(|key| *key == current)
+                });
+
+                OutputRef {
+                    output,
+                    modes,
+                    current,
+                }
+            })
+            .collect();
+
+        ListRef { outputs }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for List {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let owned = OwnedList::deserialize(deserializer)?;
+        let mut list = List::default();
+
+        for OwnedOutput {
+            mut output,
+            modes,
+            current,
+        } in owned.outputs
+        {
+            output.modes = modes
+                .into_iter()
+                .map(|mode| list.modes.insert(mode))
+                .collect();
+
+            output.current = match current {
+                Some(index) => Some(*output.modes.get(index).ok_or_else(|| {
+                    D::Error::custom(format!(
+                        "current mode {index} of {} is out of range",
+                        output.name
+                    ))
+                })?),
+                None => None,
+            };
+
+            list.outputs.insert(output);
+        }
+
+        Ok(list)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AdaptiveSyncState, List, Transform};
+
+    const LAYOUT: &str = r#"
+output "DP-1" enabled=true {
+    description make="Dell Inc." model="DELL U2723QE"
+    serial_number "ABC123"
+    physical 597 336
+    position 0 0
+    scale 1.5
+    transform "rotate90"
+    adaptive_sync "automatic"
+    modes {
+        mode 3840 2160 59997 preferred=true
+        mode 2560 1440 143999 current=true
+        mode 2560 1440 59951
+    }
+}
+output "HDMI-A-1" enabled=true {
+    description model="LG TV"
+    physical 0 0
+    position 1440 0
+    scale 1.0
+    mirroring "DP-1"
+    modes {
+        mode 1920 1080 60000 current=true preferred=true
+    }
+}
+output "eDP-1" enabled=false {
+    description model="0x1234"
+    physical 302 189
+    position 0 0
+    scale 2.0
+    modes {
+        mode 2880 1800 120000 preferred=true
+    }
+}
+"#;
+
+    fn layout() -> List {
+        List::from_kdl(LAYOUT.parse().unwrap(), true).unwrap()
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let list = layout();
+        let json = serde_json::to_string(&list).unwrap();
+        let parsed: List = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(parsed, list);
+
+        let output = parsed.output_by_name("DP-1").unwrap();
+        assert_eq!(output.transform, Some(Transform::Rotate90));
+        assert_eq!(output.adaptive_sync, Some(AdaptiveSyncState::Auto));
+        assert_eq!(output.modes.len(), 3);
+
+        let current = parsed.current_mode(output).unwrap();
+        assert_eq!(current.size, (2560, 1440));
+        assert_eq!(current.refresh_rate, 143_999);
+
+        let output = parsed.output_by_name("HDMI-A-1").unwrap();
+        assert_eq!(output.mirroring.as_deref(), Some("DP-1"));
+
+        let output = parsed.output_by_name("eDP-1").unwrap();
+        assert!(!output.enabled);
+        assert!(parsed.current_mode(output).is_none());
+    }
+
+    #[test]
+    fn current_mode_out_of_range() {
+        let json = r#"{"outputs": [{
+            "name": "DP-1", "id": null, "enabled": true, "mirroring": null, "make": null,
+            "model": "", "serial_number": null, "physical": [0, 0], "position": [0, 0],
+            "scale": 1.0, "transform": null, "adaptive_sync": null,
+            "adaptive_sync_availability": null, "vrr_range": null, "capabilities": null,
+            "modes": [], "current": 0
+        }]}"#;
+
+        assert!(serde_json::from_str::<List>(json).is_err());
+    }
+}
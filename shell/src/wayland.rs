@@ -0,0 +1,133 @@
+// Copyright 2023 System76 <info@system76.com>
+// SPDX-License-Identifier: MPL-2.0
+
+//! Conversions between the shell types and their wayland protocol counterparts.
+
+use crate::{AdaptiveSyncAvailability, AdaptiveSyncState, Transform};
+use cosmic_protocols::output_management::v1::client::zcosmic_output_head_v1::{
+    AdaptiveSyncAvailability as WlAdaptiveSyncAvailability, AdaptiveSyncStateExt,
+};
+use wayland_client::protocol::wl_output::Transform as WlTransform;
+
+impl TryFrom<WlTransform> for Transform {
+    type Error = &'static str;
+
+    fn try_from(transform: WlTransform) -> Result<Self, Self::Error> {
+        Ok(match transform {
+            WlTransform::Normal => Transform::Normal,
+            WlTransform::_90 => Transform::Rotate90,
+            WlTransform::_180 => Transform::Rotate180,
+            WlTransform::_270 => Transform::Rotate270,
+            WlTransform::Flipped => Transform::Flipped,
+            WlTransform::Flipped90 => Transform::Flipped90,
+            WlTransform::Flipped180 => Transform::Flipped180,
+            WlTransform::Flipped270 => Transform::Flipped270,
+            _ => return Err("unknown wl_transform variant"),
+        })
+    }
+}
+
+impl From<Transform> for WlTransform {
+    fn from(transform: Transform) -> Self {
+        match transform {
+            Transform::Normal => WlTransform::Normal,
+            Transform::Rotate90 => WlTransform::_90,
+            Transform::Rotate180 => WlTransform::_180,
+            Transform::Rotate270 => WlTransform::_270,
+            Transform::Flipped => WlTransform::Flipped,
+            Transform::Flipped90 => WlTransform::Flipped90,
+            Transform::Flipped180 => WlTransform::Flipped180,
+            Transform::Flipped270 => WlTransform::Flipped270,
+        }
+    }
+}
+
+impl TryFrom<AdaptiveSyncStateExt> for AdaptiveSyncState {
+    type Error = &'static str;
+
+    fn try_from(state: AdaptiveSyncStateExt) -> Result<Self, Self::Error> {
+        Ok(match state {
+            AdaptiveSyncStateExt::Always => AdaptiveSyncState::Always,
+            AdaptiveSyncStateExt::Automatic => AdaptiveSyncState::Auto,
+            AdaptiveSyncStateExt::Disabled => AdaptiveSyncState::Disabled,
+            _ => return Err("unknown adaptive_sync_state_ext variant"),
+        })
+    }
+}
+
+impl From<AdaptiveSyncState> for AdaptiveSyncStateExt {
+    fn from(state: AdaptiveSyncState) -> Self {
+        match state {
+            AdaptiveSyncState::Always => AdaptiveSyncStateExt::Always,
+            AdaptiveSyncState::Auto => AdaptiveSyncStateExt::Automatic,
+            AdaptiveSyncState::Disabled => AdaptiveSyncStateExt::Disabled,
+        }
+    }
+}
+
+impl From<WlAdaptiveSyncAvailability> for AdaptiveSyncAvailability {
+    /// Unknown availability variants are treated as unsupported.
+    fn from(available: WlAdaptiveSyncAvailability) -> Self {
+        match available {
+            WlAdaptiveSyncAvailability::Supported => AdaptiveSyncAvailability::Supported,
+            WlAdaptiveSyncAvailability::RequiresModeset => {
+                AdaptiveSyncAvailability::RequiresModeset
+            }
+            _ => AdaptiveSyncAvailability::Unsupported,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transform_round_trip() {
+        let transforms = [
+            (Transform::Normal, WlTransform::Normal),
+            (Transform::Rotate90, WlTransform::_90),
+            (Transform::Rotate180, WlTransform::_180),
+            (Transform::Rotate270, WlTransform::_270),
+            (Transform::Flipped, WlTransform::Flipped),
+            (Transform::Flipped90, WlTransform::Flipped90),
+            (Transform::Flipped180, WlTransform::Flipped180),
+            (Transform::Flipped270, WlTransform::Flipped270),
+        ];
+
+        for (transform, wl_transform) in transforms {
+            assert_eq!(WlTransform::from(transform), wl_transform);
+            assert_eq!(Transform::try_from(wl_transform), Ok(transform));
+        }
+    }
+
+    #[test]
+    fn adaptive_sync_round_trip() {
+        let states = [
+            (AdaptiveSyncState::Always, AdaptiveSyncStateExt::Always),
+            (AdaptiveSyncState::Auto, AdaptiveSyncStateExt::Automatic),
+            (AdaptiveSyncState::Disabled, AdaptiveSyncStateExt::Disabled),
+        ];
+
+        for (state, wl_state) in states {
+            assert_eq!(AdaptiveSyncStateExt::from(state), wl_state);
+            assert_eq!(AdaptiveSyncState::try_from(wl_state), Ok(state));
+        }
+    }
+
+    #[test]
+    fn adaptive_sync_availability() {
+        assert_eq!(
+            AdaptiveSyncAvailability::from(WlAdaptiveSyncAvailability::Supported),
+            AdaptiveSyncAvailability::Supported
+        );
+        assert_eq!(
+            AdaptiveSyncAvailability::from(WlAdaptiveSyncAvailability::RequiresModeset),
+            AdaptiveSyncAvailability::RequiresModeset
+        );
+        assert_eq!(
+            AdaptiveSyncAvailability::from(WlAdaptiveSyncAvailability::Unsupported),
+            AdaptiveSyncAvailability::Unsupported
+        );
+    }
+}